@@ -0,0 +1,366 @@
+//! Streams directories as zip or tar archives, built on the fly from opened files.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use chrono::prelude::*;
+use crc32fast::Hasher;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+/// The formats a directory can be downloaded as.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum ArchiveFormat {
+    Zip,
+    Tar,
+}
+
+impl ArchiveFormat {
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "application/zip",
+            ArchiveFormat::Tar => "application/x-tar",
+        }
+    }
+
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Tar => "tar",
+        }
+    }
+}
+
+/// Opens the file of an entry, which the archive only polls once it gets to the entry.
+pub(crate) type OpenEntry =
+    Pin<Box<dyn Future<Output = io::Result<Box<dyn AsyncRead + Send + Unpin>>> + Send>>;
+
+/// A file to put into an archive. `path` is relative to the archived directory.
+pub(crate) struct ArchiveEntry {
+    pub(crate) path: String,
+    pub(crate) size: u64,
+    pub(crate) modified: SystemTime,
+    pub(crate) open: OpenEntry,
+}
+
+struct CurrentEntry {
+    entry: ArchiveEntry,
+    reader: Option<Box<dyn AsyncRead + Send + Unpin>>,
+    hasher: Hasher,
+    written: u64,
+    header_offset: u64,
+}
+
+/// Reads as the archive of the given entries, opening and reading each file only when it's
+/// its turn, so no more than one of them is open at a time.
+///
+/// Zip archives are written without compression and with data descriptors, since the CRC of
+/// a file is only known after streaming it. Neither format supports files of 4GiB or more,
+/// and zip archives are limited to 65535 files and 4GiB in total.
+pub(crate) struct ArchiveStream {
+    format: ArchiveFormat,
+    entries: VecDeque<ArchiveEntry>,
+    current: Option<CurrentEntry>,
+    pending: Vec<u8>,
+    pending_pos: usize,
+    central_directory: Vec<u8>,
+    count: u16,
+    offset: u64,
+    finished: bool,
+}
+
+impl ArchiveStream {
+    /// Fails if the entries don't fit into the format, as far as their sizes tell upfront.
+    pub(crate) fn new(format: ArchiveFormat, entries: Vec<ArchiveEntry>) -> io::Result<Self> {
+        if let Some(entry) = entries.iter().find(|entry| entry.size > u64::from(u32::MAX)) {
+            return Err(io::Error::other(format!("{} is too large to archive", entry.path)));
+        }
+        if format == ArchiveFormat::Zip {
+            if entries.len() > usize::from(u16::MAX) {
+                return Err(io::Error::other("too many files for a zip archive"));
+            }
+            // Local headers, data and descriptors, and the central directory after them
+            let total: u64 = entries
+                .iter()
+                .map(|entry| 30 + 16 + 46 + 2 * entry.path.len() as u64 + entry.size)
+                .sum();
+            if total + 22 > u64::from(u32::MAX) {
+                return Err(io::Error::other("too large for a zip archive"));
+            }
+        }
+
+        Ok(ArchiveStream {
+            format,
+            entries: entries.into(),
+            current: None,
+            pending: Vec::new(),
+            pending_pos: 0,
+            central_directory: Vec::new(),
+            count: 0,
+            offset: 0,
+            finished: false,
+        })
+    }
+
+    fn push_pending(&mut self, bytes: Vec<u8>) {
+        self.offset += bytes.len() as u64;
+        self.pending = bytes;
+        self.pending_pos = 0;
+    }
+
+    fn start_entry(&mut self, entry: ArchiveEntry) -> io::Result<()> {
+        let header = match self.format {
+            ArchiveFormat::Zip => zip_local_header(&entry)?,
+            ArchiveFormat::Tar => tar_header(&entry)?,
+        };
+        let header_offset = self.offset;
+        if self.format == ArchiveFormat::Zip && header_offset > u64::from(u32::MAX) {
+            return Err(io::Error::other("files grew too large for a zip archive"));
+        }
+        self.push_pending(header);
+        self.current = Some(CurrentEntry {
+            entry,
+            reader: None,
+            hasher: Hasher::new(),
+            written: 0,
+            header_offset,
+        });
+        Ok(())
+    }
+
+    fn finish_entry(&mut self, current: CurrentEntry) -> io::Result<()> {
+        let trailer = match self.format {
+            ArchiveFormat::Zip => {
+                let crc = current.hasher.finalize();
+                let size = u32::try_from(current.written).map_err(|_| {
+                    io::Error::other(format!("{} grew too large to archive", current.entry.path))
+                })?;
+                let (time, date) = dos_date_time(current.entry.modified);
+                let name = current.entry.path.as_bytes();
+
+                let mut descriptor = Vec::with_capacity(16);
+                descriptor.write_u32::<LittleEndian>(0x0807_4b50)?;
+                descriptor.write_u32::<LittleEndian>(crc)?;
+                descriptor.write_u32::<LittleEndian>(size)?;
+                descriptor.write_u32::<LittleEndian>(size)?;
+
+                let cd = &mut self.central_directory;
+                cd.write_u32::<LittleEndian>(0x0201_4b50)?;
+                cd.write_u16::<LittleEndian>(20)?;
+                cd.write_u16::<LittleEndian>(20)?;
+                cd.write_u16::<LittleEndian>(ZIP_FLAGS)?;
+                cd.write_u16::<LittleEndian>(0)?;
+                cd.write_u16::<LittleEndian>(time)?;
+                cd.write_u16::<LittleEndian>(date)?;
+                cd.write_u32::<LittleEndian>(crc)?;
+                cd.write_u32::<LittleEndian>(size)?;
+                cd.write_u32::<LittleEndian>(size)?;
+                cd.write_u16::<LittleEndian>(name.len() as u16)?;
+                // extra field length, comment length, disk number, internal attributes
+                cd.write_u16::<LittleEndian>(0)?;
+                cd.write_u16::<LittleEndian>(0)?;
+                cd.write_u16::<LittleEndian>(0)?;
+                cd.write_u16::<LittleEndian>(0)?;
+                cd.write_u32::<LittleEndian>(0)?;
+                cd.write_u32::<LittleEndian>(current.header_offset as u32)?;
+                cd.write_all(name)?;
+
+                descriptor
+            }
+            ArchiveFormat::Tar => {
+                // The size has been written upfront and the reader is cut to it, so a file
+                // which shrank can't be archived.
+                if current.written != current.entry.size {
+                    return Err(io::Error::other(format!(
+                        "{} changed while archiving",
+                        current.entry.path
+                    )));
+                }
+                vec![0; padding(current.written)]
+            }
+        };
+        self.count += 1;
+        self.push_pending(trailer);
+        Ok(())
+    }
+
+    fn finish_archive(&mut self) -> io::Result<()> {
+        let footer = match self.format {
+            ArchiveFormat::Zip => {
+                // Files may have grown since their sizes were checked
+                let end = self.offset + self.central_directory.len() as u64;
+                if end > u64::from(u32::MAX) {
+                    return Err(io::Error::other("files grew too large for a zip archive"));
+                }
+                let mut footer = std::mem::take(&mut self.central_directory);
+                let cd_size = footer.len() as u32;
+                footer.write_u32::<LittleEndian>(0x0605_4b50)?;
+                footer.write_u16::<LittleEndian>(0)?;
+                footer.write_u16::<LittleEndian>(0)?;
+                footer.write_u16::<LittleEndian>(self.count)?;
+                footer.write_u16::<LittleEndian>(self.count)?;
+                footer.write_u32::<LittleEndian>(cd_size)?;
+                footer.write_u32::<LittleEndian>(self.offset as u32)?;
+                footer.write_u16::<LittleEndian>(0)?;
+                footer
+            }
+            // Two empty blocks mark the end of a tar archive
+            ArchiveFormat::Tar => vec![0; 1024],
+        };
+        self.push_pending(footer);
+        self.finished = true;
+        Ok(())
+    }
+}
+
+impl AsyncRead for ArchiveStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        loop {
+            // Headers and trailers which still need to be handed out come first
+            if this.pending_pos < this.pending.len() {
+                let len = buf.remaining().min(this.pending.len() - this.pending_pos);
+                buf.put_slice(&this.pending[this.pending_pos..this.pending_pos + len]);
+                this.pending_pos += len;
+                return Poll::Ready(Ok(()));
+            }
+
+            if let Some(current) = this.current.as_mut() {
+                let reader = match current.reader {
+                    Some(ref mut reader) => reader,
+                    None => match current.entry.open.as_mut().poll(cx) {
+                        // Tar headers declare the size upfront, so bytes a file gained since
+                        // would run into the next header
+                        Poll::Ready(Ok(reader)) if this.format == ArchiveFormat::Tar => {
+                            current.reader.insert(Box::new(reader.take(current.entry.size)))
+                        }
+                        Poll::Ready(Ok(reader)) => current.reader.insert(reader),
+                        Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                        Poll::Pending => return Poll::Pending,
+                    },
+                };
+                let before = buf.filled().len();
+                match Pin::new(reader).poll_read(cx, buf) {
+                    Poll::Ready(Ok(())) => {}
+                    other => return other,
+                }
+                let read = &buf.filled()[before..];
+                if !read.is_empty() {
+                    current.hasher.update(read);
+                    current.written += read.len() as u64;
+                    this.offset += read.len() as u64;
+                    return Poll::Ready(Ok(()));
+                }
+
+                let current = this.current.take().unwrap();
+                this.finish_entry(current)?;
+                continue;
+            }
+
+            if let Some(entry) = this.entries.pop_front() {
+                this.start_entry(entry)?;
+                continue;
+            }
+
+            if !this.finished {
+                this.finish_archive()?;
+                continue;
+            }
+
+            return Poll::Ready(Ok(()));
+        }
+    }
+}
+
+/// Sizes and CRCs follow the data (bit 3) and names are UTF-8 (bit 11).
+const ZIP_FLAGS: u16 = 0x0808;
+
+fn zip_local_header(entry: &ArchiveEntry) -> io::Result<Vec<u8>> {
+    let name = entry.path.as_bytes();
+    let (time, date) = dos_date_time(entry.modified);
+
+    let mut header = Vec::with_capacity(30 + name.len());
+    header.write_u32::<LittleEndian>(0x0403_4b50)?;
+    header.write_u16::<LittleEndian>(20)?;
+    header.write_u16::<LittleEndian>(ZIP_FLAGS)?;
+    header.write_u16::<LittleEndian>(0)?;
+    header.write_u16::<LittleEndian>(time)?;
+    header.write_u16::<LittleEndian>(date)?;
+    // crc, compressed and uncompressed size are in the data descriptor
+    header.write_u32::<LittleEndian>(0)?;
+    header.write_u32::<LittleEndian>(0)?;
+    header.write_u32::<LittleEndian>(0)?;
+    header.write_u16::<LittleEndian>(name.len() as u16)?;
+    header.write_u16::<LittleEndian>(0)?;
+    header.write_all(name)?;
+    Ok(header)
+}
+
+fn dos_date_time(time: SystemTime) -> (u16, u16) {
+    let time: DateTime<Utc> = DateTime::from(time);
+    // DOS dates can't go before 1980
+    if time.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let dos_time = (time.hour() << 11) | (time.minute() << 5) | (time.second() / 2);
+    let dos_date = (((time.year() - 1980) as u32) << 9) | (time.month() << 5) | time.day();
+    (dos_time as u16, dos_date as u16)
+}
+
+fn tar_header(entry: &ArchiveEntry) -> io::Result<Vec<u8>> {
+    let mut header = vec![0u8; 512];
+
+    // Paths longer than the name field are split into the ustar prefix
+    let path = entry.path.as_bytes();
+    if path.len() <= 100 {
+        header[..path.len()].copy_from_slice(path);
+    } else {
+        let split = path[..path.len().min(156)]
+            .iter()
+            .rposition(|b| *b == b'/')
+            .filter(|i| path.len() - i - 1 <= 100)
+            .ok_or_else(|| io::Error::other(format!("{} is too long to archive", entry.path)))?;
+        header[345..345 + split].copy_from_slice(&path[..split]);
+        header[..path.len() - split - 1].copy_from_slice(&path[split + 1..]);
+    }
+
+    let modified: DateTime<Utc> = DateTime::from(entry.modified);
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], entry.size);
+    write_octal(&mut header[136..148], modified.timestamp().max(0) as u64);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is calculated with the checksum field itself filled with spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|b| u32::from(*b)).sum();
+    write_octal(&mut header[148..155], u64::from(checksum));
+    Ok(header)
+}
+
+/// Writes `value` as a zero padded, NUL terminated octal number filling `field`.
+fn write_octal(field: &mut [u8], value: u64) {
+    let len = field.len() - 1;
+    let digits = format!("{:0width$o}", value, width = len);
+    field[..len].copy_from_slice(digits.as_bytes());
+    field[len] = 0;
+}
+
+/// Returns the number of bytes needed to fill up the last 512 byte block of a file.
+fn padding(size: u64) -> usize {
+    ((512 - size % 512) % 512) as usize
+}
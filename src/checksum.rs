@@ -0,0 +1,84 @@
+//! Computes checksums of files for `?checksum=` queries, see
+//! `OptionsBuilder::allow_checksum_query`.
+
+use sha2::{Digest, Sha256};
+
+/// A checksum being computed over the bytes it's fed.
+pub(crate) enum Checksum {
+    Sha256(Sha256),
+    Crc32(crc32fast::Hasher),
+}
+
+impl Checksum {
+    /// Starts a checksum of the named algorithm, `sha256` or `crc32`.
+    pub(crate) fn new(algorithm: &str) -> Option<Self> {
+        match algorithm {
+            "sha256" => Some(Checksum::Sha256(Sha256::new())),
+            "crc32" => Some(Checksum::Crc32(crc32fast::Hasher::new())),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Checksum::Sha256(sha) => sha.update(bytes),
+            Checksum::Crc32(crc) => crc.update(bytes),
+        }
+    }
+
+    /// Finishes the checksum as lowercase hex.
+    pub(crate) fn hex(self) -> String {
+        let digest = match self {
+            Checksum::Sha256(sha) => sha.finalize().to_vec(),
+            Checksum::Crc32(crc) => crc.finalize().to_be_bytes().to_vec(),
+        };
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha256(bytes: &[u8]) -> String {
+        let mut checksum = Checksum::new("sha256").unwrap();
+        checksum.update(bytes);
+        checksum.hex()
+    }
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+
+        // Fed in pieces across block boundaries, it's still the same
+        let bytes = vec![b'a'; 1000];
+        let mut checksum = Checksum::new("sha256").unwrap();
+        for chunk in bytes.chunks(7) {
+            checksum.update(chunk);
+        }
+        assert_eq!(checksum.hex(), sha256(&bytes));
+        assert_eq!(
+            sha256(&bytes),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+
+    #[test]
+    fn test_crc32() {
+        let mut checksum = Checksum::new("crc32").unwrap();
+        checksum.update(b"123456789");
+        assert_eq!(checksum.hex(), "cbf43926");
+        assert!(Checksum::new("md4").is_none());
+    }
+}
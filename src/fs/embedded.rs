@@ -5,7 +5,8 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
 use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::SystemTime;
 use walkdir::WalkDir;
 
@@ -55,21 +56,108 @@ pub struct EmbeddedFileSystem {
 
 impl EmbeddedFileSystem {
     pub fn from_bytes(bytes: &'static [u8]) -> Result<Self, Box<dyn Error>> {
-        let package = Package::from_bytes(bytes)?;
+        let package = Package::from_data(PackageData::Static(bytes))?;
         Ok(EmbeddedFileSystem { package })
     }
+
+    /// Loads a package which isn't embedded in the binary, e.g. one read from a file at
+    /// runtime.
+    pub fn from_vec(bytes: Vec<u8>) -> Result<Self, Box<dyn Error>> {
+        let package = Package::from_data(PackageData::Owned(bytes.into()))?;
+        Ok(EmbeddedFileSystem { package })
+    }
+
+    /// Memory-maps the package file at `path` instead of reading it, so only the parts of
+    /// large packages which are actually served are loaded into memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the filesystem is in use, since
+    /// changes show up in the mapped data without any synchronization.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn map_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let map = memmap2::Mmap::map(&file)?;
+        let package = Package::from_data(PackageData::Mapped(Arc::new(map)))?;
+        Ok(EmbeddedFileSystem { package })
+    }
+
+    /// Reads a whole package from `reader`, see `from_vec`.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        EmbeddedFileSystem::from_vec(bytes)
+    }
+
+    /// Checks that every file of the package lies within its data, so a corrupt package can
+    /// be rejected at startup instead of failing on the first request.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        for (path, file) in self.package.files.iter() {
+            self.package.open(path)?;
+            if let Some((len, start)) = file.gzip {
+                self.package.slice(len, start)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks every file of the package like `validate`, and that its bytes still match the
+    /// checksum stored when packing them. Gzip variants have to decompress to the same bytes.
+    /// Unlike `validate` it doesn't stop at the first corrupt file, the error names all of
+    /// them.
+    ///
+    /// Packages written without `PackageBuilder::checksums`, like the ones of older versions,
+    /// only have their bounds checked, and gzip variants whether they decompress fine.
+    pub fn validate_all(&self) -> Result<(), Box<dyn Error>> {
+        let mut corrupt: Vec<&str> = self
+            .package
+            .files
+            .iter()
+            .filter(|(path, file)| {
+                let intact = match (self.package.open(path), file.checksum) {
+                    (Ok(bytes), Some(checksum)) => {
+                        crc32fast::hash(bytes.get_ref().as_ref()) == checksum
+                    }
+                    (Ok(_), None) => true,
+                    (Err(_), _) => false,
+                };
+                let variant = file.gzip.map(|(len, start)| self.package.slice(len, start));
+                let variant_intact = match variant {
+                    Some(Ok(variant)) => gzip_intact(variant.get_ref().as_ref(), file),
+                    Some(Err(_)) => false,
+                    None => true,
+                };
+                !intact || !variant_intact
+            })
+            .map(|(path, _)| path.as_str())
+            .collect();
+        if corrupt.is_empty() {
+            return Ok(());
+        }
+
+        corrupt.sort_unstable();
+        Err(Box::new(crate::Error::new(&format!(
+            "corrupt files in the package: {}",
+            corrupt.join(", ")
+        ))))
+    }
+
+    /// Returns the paths of all files in the package, sorted.
+    pub fn paths(&self) -> Vec<&str> {
+        let mut paths: Vec<&str> = self.package.files.keys().map(String::as_str).collect();
+        paths.sort_unstable();
+        paths
+    }
 }
 
 #[rocket::async_trait]
 impl FileSystem for EmbeddedFileSystem {
-    type Read = Cursor<&'static [u8]>;
+    type Read = Cursor<EmbeddedFile>;
 
     async fn is_file<P>(&self, path: P) -> bool
         where P: AsRef<Path> + Send
     {
-        self.package
-            .files
-            .contains_key(path.as_ref().to_str().unwrap())
+        self.package.file(path).is_some()
     }
 
     async fn is_dir<P>(&self, path: P) -> bool
@@ -81,7 +169,7 @@ impl FileSystem for EmbeddedFileSystem {
     async fn last_modified<P>(&self, path: P) -> Result<SystemTime, Box<dyn Error>>
         where P: AsRef<Path> + Send
     {
-        match self.package.files.get(path.as_ref().to_str().unwrap()) {
+        match self.package.file(path) {
             Some(file) => Ok(file.last_modified.into()),
             None => Err(Box::new(crate::Error::new("file does not exist"))),
         }
@@ -90,7 +178,7 @@ impl FileSystem for EmbeddedFileSystem {
     async fn size<P>(&self, path: P) -> Result<u64, Box<dyn Error>>
         where P: AsRef<Path> + Send
     {
-        match self.package.files.get(path.as_ref().to_str().unwrap()) {
+        match self.package.file(path) {
             Some(file) => Ok(file.len),
             None => Err(Box::new(crate::Error::new("file does not exist"))),
         }
@@ -110,12 +198,23 @@ impl FileSystem for EmbeddedFileSystem {
         Ok(reader)
     }
 
+    fn static_bytes<P>(&self, path: P) -> Option<&'static [u8]>
+        where P: AsRef<Path>
+    {
+        match self.package.open(path).ok()?.into_inner() {
+            EmbeddedFile {
+                data: PackageData::Static(data),
+                start,
+                end,
+            } => Some(&data[start..end]),
+            EmbeddedFile { .. } => None,
+        }
+    }
+
     async fn path_valid<P>(&self, path: P) -> bool
         where P: AsRef<Path> + Send
     {
-        self.package
-            .files
-            .contains_key(path.as_ref().to_str().unwrap())
+        self.package.file(&path).is_some() || self.package.is_dir(path)
     }
 
     async fn entries<P>(&self, path: P) -> Result<Vec<Entry>, Box<dyn Error>>
@@ -123,30 +222,95 @@ impl FileSystem for EmbeddedFileSystem {
     {
         self.package.entries(path)
     }
+
+    async fn walk<P>(&self, path: P) -> Result<Vec<String>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        Ok(self.package.walk(path))
+    }
+}
+
+/// The bytes of a package, either embedded in the binary or loaded at runtime.
+#[derive(Clone)]
+enum PackageData {
+    Static(&'static [u8]),
+    Owned(Arc<[u8]>),
+    #[cfg(feature = "mmap")]
+    Mapped(Arc<memmap2::Mmap>),
+}
+
+impl AsRef<[u8]> for PackageData {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            PackageData::Static(data) => data,
+            PackageData::Owned(data) => data,
+            #[cfg(feature = "mmap")]
+            PackageData::Mapped(data) => data,
+        }
+    }
+}
+
+/// The contents of a single file in a package, sharing the package's bytes.
+#[derive(Clone)]
+pub struct EmbeddedFile {
+    data: PackageData,
+    start: usize,
+    end: usize,
+}
+
+impl AsRef<[u8]> for EmbeddedFile {
+    fn as_ref(&self) -> &[u8] {
+        &self.data.as_ref()[self.start..self.end]
+    }
 }
 
 struct Package {
     files: HashMap<String, InternalFile>,
-    data: &'static [u8],
+    data: PackageData,
+    /// Where the file data starts in `data`, right after the metadata.
+    data_start: usize,
 }
 
+#[derive(Clone, Copy)]
 struct InternalFile {
     last_modified: DateTime<Utc>,
     len: u64,
     start: u64,
+    /// The length and start of the file's gzip variant, if the package has one.
+    gzip: Option<(u64, u64)>,
+    /// The CRC32 of the file's bytes, which packages written before them don't have.
+    checksum: Option<u32>,
 }
 
+/// Set in the metadata length of packages whose entries carry a gzip variant, which older
+/// packages can't have set since their metadata would exceed any package.
+const GZIP_VARIANTS: u64 = 1 << 63;
+
+/// Set in the metadata length of packages whose entries end with a checksum of the file.
+const CHECKSUMS: u64 = 1 << 62;
+
 impl Package {
-    pub fn from_bytes(bytes: &'static [u8]) -> Result<Self, Box<dyn Error>> {
+    fn from_data(data: PackageData) -> Result<Self, Box<dyn Error>> {
+        let bytes = data.as_ref();
         let mut cursor = Cursor::new(bytes);
         let meta_len = cursor.read_u64::<BigEndian>()?;
+        let has_gzip = meta_len & GZIP_VARIANTS != 0;
+        let has_checksums = meta_len & CHECKSUMS != 0;
+        let meta_len = meta_len & !(GZIP_VARIANTS | CHECKSUMS);
+
+        // A corrupt length must fail right away instead of reading entries until it's reached
+        let data_start = meta_len
+            .checked_add(8)
+            .filter(|data_start| *data_start <= bytes.len() as u64)
+            .ok_or_else(|| crate::Error::new("package metadata exceeds the package"))?
+            as usize;
 
         let mut files = HashMap::new();
         let mut read = 0;
 
         while read < meta_len {
             let cursor_start = cursor.position();
-            let path_len = cursor.read_u64::<BigEndian>()? as u64;
+            let path_len = cursor.read_u64::<BigEndian>()?;
             let mut path = String::new();
             let cursor_clone = cursor.clone();
             let mut path_reader = cursor_clone.take(path_len);
@@ -154,10 +318,26 @@ impl Package {
             cursor.seek(SeekFrom::Current(path_len as i64))?;
 
             let last_modified_seconds = cursor.read_i64::<BigEndian>()?;
-            let last_modified: DateTime<Utc> = Utc.timestamp(last_modified_seconds, 0);
+            let last_modified: DateTime<Utc> = Utc
+                .timestamp_opt(last_modified_seconds, 0)
+                .single()
+                .ok_or_else(|| crate::Error::new("invalid last modified timestamp"))?;
 
             let len = cursor.read_u64::<BigEndian>()?;
             let start = cursor.read_u64::<BigEndian>()?;
+            // A length of 0 means the file is stored without a variant
+            let gzip = if has_gzip {
+                let gzip_len = cursor.read_u64::<BigEndian>()?;
+                let gzip_start = cursor.read_u64::<BigEndian>()?;
+                Some((gzip_len, gzip_start)).filter(|(len, _)| *len > 0)
+            } else {
+                None
+            };
+            let checksum = if has_checksums {
+                Some(cursor.read_u32::<BigEndian>()?)
+            } else {
+                None
+            };
 
             let cursor_end = cursor.position();
 
@@ -169,91 +349,97 @@ impl Package {
                     last_modified,
                     len,
                     start,
+                    gzip,
+                    checksum,
                 },
             );
         }
 
-        let data = &bytes[(meta_len + 8) as usize..];
-        Ok(Package { files, data })
+        Ok(Package {
+            files,
+            data,
+            data_start,
+        })
+    }
+
+    #[cfg(test)]
+    fn from_bytes(bytes: &'static [u8]) -> Result<Self, Box<dyn Error>> {
+        Package::from_data(PackageData::Static(bytes))
     }
 
-    fn open<P>(&self, path: P) -> Result<Cursor<&'static [u8]>, Box<dyn Error>>
+    /// Looks up the file at `path`. The gzip variant of a file `x` is served as `x.gz`, unless
+    /// the package has a file of that name itself.
+    fn file<P: AsRef<Path>>(&self, path: P) -> Option<InternalFile> {
+        let path = path.as_ref().to_str().unwrap();
+        if let Some(file) = self.files.get(path) {
+            return Some(*file);
+        }
+        let file = self.files.get(path.strip_suffix(".gz")?)?;
+        let (len, start) = file.gzip?;
+        Some(InternalFile {
+            len,
+            start,
+            gzip: None,
+            checksum: None,
+            ..*file
+        })
+    }
+
+    fn open<P>(&self, path: P) -> Result<Cursor<EmbeddedFile>, Box<dyn Error>>
     where
         P: AsRef<Path>,
     {
-        match self.files.get(path.as_ref().to_str().unwrap()) {
-            Some(file) => {
-                let start = file.start as usize;
-                let end = (file.start + file.len) as usize;
-                let slice = &self.data[start..end];
-                Ok(Cursor::new(slice))
-            }
+        match self.file(path) {
+            Some(file) => self.slice(file.len, file.start),
             None => Err(Box::new(crate::Error::new("file does not exist"))),
         }
     }
 
-    fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
-        // / is always a dir
-        if let Some("/") = path.as_ref().to_str() {
-            return true;
-        }
-
-        let mut path_str = path.as_ref().to_str().unwrap().to_string();
-
-        // The path most likely starts with a / but our package paths do not
-        // so we remove it here if it exists
-        if path_str.starts_with('/') {
-            path_str = path_str.replacen('/', "", 1);
+    /// Reads the `len` bytes at `start` of the file data.
+    fn slice(&self, len: u64, start: u64) -> Result<Cursor<EmbeddedFile>, Box<dyn Error>> {
+        // Corrupt or crafted metadata must neither overflow nor slice out of bounds
+        let data_len = (self.data.as_ref().len() - self.data_start) as u64;
+        match start.checked_add(len).filter(|end| *end <= data_len) {
+            Some(end) => Ok(Cursor::new(EmbeddedFile {
+                data: self.data.clone(),
+                start: self.data_start + start as usize,
+                end: self.data_start + end as usize,
+            })),
+            None => Err(Box::new(crate::Error::new("file exceeds the package data"))),
         }
+    }
 
-        for (k, _v) in self.files.iter() {
-            // Skip every file which doesn't match our path
-            if !k.starts_with(&path_str) {
-                continue;
-            }
+    fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        let prefix = dir_prefix(path.as_ref());
 
-            // If the right starts with a slash, it is a directory
-            let right = k.replacen(&path_str, "", 1);
-            if right.starts_with('/') {
-                return true;
-            }
+        // The root is always a dir
+        if prefix.is_empty() {
+            return true;
         }
 
-        false
+        // Every file below the path makes it a directory
+        self.files.keys().any(|k| k.starts_with(&prefix))
     }
 
     fn entries<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Entry>, Box<dyn Error>> {
-        let mut path_str = path.as_ref().to_str().unwrap().to_string();
-
-        // The path most likely starts with a / but our package paths do not
-        // so we remove it here if it exists
-        if path_str.starts_with('/') {
-            path_str = path_str.replacen('/', "", 1);
-        }
+        let prefix = dir_prefix(path.as_ref());
 
         let mut entries = Vec::new();
         for (k, v) in self.files.iter() {
-            // Skip every file which doesn't match our path
-            if !k.starts_with(&path_str) {
-                continue;
-            }
+            // Skip every file which isn't in our directory
+            let right = match k.strip_prefix(&prefix) {
+                Some(right) => right,
+                None => continue,
+            };
 
-            // If the right side starts with a slash we are pretty much in the directory
-            let right = k.replacen(&path_str, "", 1);
-            if right.starts_with('/') {
-                let right = right.replacen('/', "", 1);
-                // If the right side still contains a slash, we still have sub-directories
-                if right.contains('/') {
-                    let dir_name = right.splitn(2, '/').collect::<Vec<&str>>()[0];
-                    entries.push(Entry::Dir(dir_name.to_string()));
-                } else {
-                    // This can't possibly go wrong
-                    entries.push(Entry::File(
-                        right.to_string(),
-                        v.len,
-                        v.last_modified.into(),
-                    ));
-                }
+            // If the right side still contains a slash, we still have sub-directories
+            match right.split_once('/') {
+                Some((dir_name, _)) => entries.push(Entry::Dir(dir_name.to_string())),
+                None => entries.push(Entry::File(
+                    right.to_string(),
+                    v.len,
+                    v.last_modified.into(),
+                )),
             }
         }
 
@@ -261,9 +447,35 @@ impl Package {
             Entry::File(ref name, _, _) => name.to_string(),
             Entry::Dir(ref name) => name.to_string(),
         });
+        // Every file in a sub-directory names it once
+        entries.dedup_by(|a, b| matches!((a, b), (Entry::Dir(a), Entry::Dir(b)) if a == b));
 
         Ok(entries)
     }
+
+    fn walk<P: AsRef<Path>>(&self, path: P) -> Vec<String> {
+        // The paths are flat already, every one below the directory is one of its files
+        let prefix = dir_prefix(path.as_ref());
+        let mut paths: Vec<String> = self
+            .files
+            .keys()
+            .filter(|k| k.starts_with(&prefix))
+            .cloned()
+            .collect();
+        paths.sort_unstable();
+        paths
+    }
+}
+
+/// Turns a requested directory like `/inner/` into the prefix its package paths start with,
+/// `inner/`, or an empty one for the root.
+fn dir_prefix(path: &Path) -> String {
+    let path = path.to_str().unwrap().trim_matches('/');
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", path)
+    }
 }
 
 /// Writes a package to the given writer. The paths will be as given in `input_files`.
@@ -271,6 +483,94 @@ impl Package {
 ///
 /// Most likely you want to use `create_package_from_dir` instead.
 pub fn write_package<W, T, P>(root: P, input_files: &[T], writer: &mut W) -> Result<(), Box<dyn Error>>
+where
+    P: AsRef<Path>,
+    W: Write + WriteBytesExt,
+    T: AsRef<str> + Clone + Ord,
+{
+    PackageBuilder::new().write(root, input_files, writer)
+}
+
+/// Like `write_package`, but also stores a gzip-compressed variant of every file which gzip
+/// makes smaller. With `Options::precompressed_variants` enabled, the variant of `x` is then
+/// read as `x.gz` for clients accepting gzip, without compressing anything at runtime.
+#[cfg(feature = "content_encoding")]
+pub fn write_package_with_gzip<W, T, P>(
+    root: P,
+    input_files: &[T],
+    writer: &mut W,
+) -> Result<(), Box<dyn Error>>
+where
+    P: AsRef<Path>,
+    W: Write + WriteBytesExt,
+    T: AsRef<str> + Clone + Ord,
+{
+    PackageBuilder::new().gzip(true).write(root, input_files, writer)
+}
+
+#[cfg(feature = "content_encoding")]
+fn gzip(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+/// Whether the gzip `variant` of `file` decompresses to as many bytes as the file has, with
+/// the file's checksum if the package has one.
+#[cfg(feature = "content_encoding")]
+fn gzip_intact(variant: &[u8], file: &InternalFile) -> bool {
+    let mut decoder = flate2::read::GzDecoder::new(variant);
+    let mut crc = crc32fast::Hasher::new();
+    let mut buf = [0; 8192];
+    let mut len = 0;
+    loop {
+        match decoder.read(&mut buf) {
+            // The decoder checks the CRC32 in the gzip trailer before reporting the end
+            Ok(0) => break,
+            Ok(read) => {
+                crc.update(&buf[..read]);
+                len += read as u64;
+                // A broken variant may inflate to anything, there's no point in going on
+                if len > file.len {
+                    return false;
+                }
+            }
+            Err(_) => return false,
+        }
+    }
+    let crc = crc.finalize();
+    len == file.len && file.checksum.is_none_or(|checksum| checksum == crc)
+}
+
+/// Without the `content_encoding` feature there's nothing to decompress the gzip `variant` of
+/// `file` with, so only the CRC32 and length in its trailer are compared to the file's.
+#[cfg(not(feature = "content_encoding"))]
+fn gzip_intact(variant: &[u8], file: &InternalFile) -> bool {
+    let mut trailer = match variant.len().checked_sub(8) {
+        Some(start) => &variant[start..],
+        None => return false,
+    };
+    let (crc, len) = match (
+        trailer.read_u32::<byteorder::LittleEndian>(),
+        trailer.read_u32::<byteorder::LittleEndian>(),
+    ) {
+        (Ok(crc), Ok(len)) => (crc, len),
+        _ => return false,
+    };
+    // The length is only kept modulo 2^32
+    len == file.len as u32 && file.checksum.is_none_or(|checksum| checksum == crc)
+}
+
+/// Compresses a file's bytes into its variant.
+type Compress<'a> = &'a dyn Fn(&[u8]) -> io::Result<Vec<u8>>;
+
+fn write_package_data<W, T, P>(
+    root: P,
+    input_files: &[T],
+    writer: &mut W,
+    compress: Option<Compress>,
+    with_checksums: bool,
+) -> Result<(), Box<dyn Error>>
 where
     P: AsRef<Path>,
     W: Write + WriteBytesExt,
@@ -281,11 +581,14 @@ where
 
     let mut file_sizes = Vec::new();
     let mut file_modification_times = Vec::new();
+    let mut checksums = Vec::new();
+    // Variants are kept in memory until the identity bytes of all files have been written
+    let mut variants = Vec::new();
     let mut meta_len = 0;
     for f in &files {
         // 8 * 4 = 32 cause of last_modified + path_len + start + len which are all 64bit
         meta_len += 32;
-        meta_len += f.as_ref().as_bytes().len();
+        meta_len += f.as_ref().len();
 
         let meta = root.as_ref().join(f.as_ref()).metadata()?;
         let file_size = meta.len();
@@ -293,14 +596,39 @@ where
 
         let mod_time = meta.modified()?;
         file_modification_times.push(mod_time);
+
+        if compress.is_none() && !with_checksums {
+            continue;
+        }
+        let bytes = std::fs::read(root.as_ref().join(f.as_ref()))?;
+        if with_checksums {
+            // 4 more for the 32bit checksum
+            meta_len += 4;
+            checksums.push(crc32fast::hash(&bytes));
+        }
+        if let Some(compress) = compress {
+            // 8 * 2 more for the length and start of the variant
+            meta_len += 16;
+            let variant = compress(&bytes)?;
+            variants.push(Some(variant).filter(|variant| (variant.len() as u64) < file_size));
+        }
     }
+    let variants_start: u64 = file_sizes.iter().sum();
+    let mut variant_offset = variants_start;
 
     let mut data_offset = 0;
-    writer.write_u64::<BigEndian>(meta_len as u64)?;
+    let mut flags = 0;
+    if compress.is_some() {
+        flags |= GZIP_VARIANTS;
+    }
+    if with_checksums {
+        flags |= CHECKSUMS;
+    }
+    writer.write_u64::<BigEndian>(meta_len as u64 | flags)?;
 
     for (i, f) in files.iter().enumerate() {
         // written in the following order: path_len, path, last_modified, len, start
-        writer.write_u64::<BigEndian>(f.as_ref().as_bytes().len() as u64)?;
+        writer.write_u64::<BigEndian>(f.as_ref().len() as u64)?;
         write!(writer, "{}", f.as_ref().replace('\\', "/"))?;
 
         let last_modified: DateTime<Utc> = DateTime::from(file_modification_times[i]);
@@ -311,6 +639,18 @@ where
 
         writer.write_u64::<BigEndian>(data_offset as u64)?;
 
+        if compress.is_some() {
+            // written after the entry: variant_len, variant_start
+            let variant_len = variants[i].as_ref().map_or(0, |v| v.len() as u64);
+            writer.write_u64::<BigEndian>(variant_len)?;
+            writer.write_u64::<BigEndian>(variant_offset)?;
+            variant_offset += variant_len;
+        }
+        // and last the checksum
+        if with_checksums {
+            writer.write_u32::<BigEndian>(checksums[i])?;
+        }
+
         data_offset += (*file_size) as usize;
     }
 
@@ -318,6 +658,9 @@ where
         let mut file = File::open(root.as_ref().join(f.as_ref()))?;
         io::copy(&mut file, writer)?;
     }
+    for variant in variants.iter().flatten() {
+        writer.write_all(variant)?;
+    }
 
     Ok(())
 }
@@ -342,6 +685,94 @@ where
     P: AsRef<Path>,
     W: Write,
 {
+    PackageBuilder::new().create_from_dir(dir, writer)
+}
+
+/// Like `create_package_from_dir`, but also stores gzip variants, see `write_package_with_gzip`.
+#[cfg(feature = "content_encoding")]
+pub fn create_package_from_dir_with_gzip<P, W>(dir: P, writer: &mut W) -> Result<(), Box<dyn Error>>
+where
+    P: AsRef<Path>,
+    W: Write,
+{
+    PackageBuilder::new().gzip(true).create_from_dir(dir, writer)
+}
+
+/// Writes packages like `write_package` and `create_package_from_dir`, optionally storing
+/// more along with the files.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use rocket_static_fs::fs::PackageBuilder;
+///
+/// fn main() {
+///     let mut f = File::create("assets.pack").unwrap();
+///     PackageBuilder::new()
+///         .checksums(true)
+///         .create_from_dir("assets", &mut f)
+///         .unwrap();
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct PackageBuilder {
+    #[cfg(feature = "content_encoding")]
+    gzip: bool,
+    checksums: bool,
+}
+
+impl PackageBuilder {
+    pub fn new() -> Self {
+        PackageBuilder::default()
+    }
+
+    /// Stores gzip variants, see `write_package_with_gzip`.
+    #[cfg(feature = "content_encoding")]
+    pub fn gzip(mut self, enabled: bool) -> Self {
+        self.gzip = enabled;
+        self
+    }
+
+    /// Stores the CRC32 of every file, which `EmbeddedFileSystem::validate_all` checks the
+    /// files against. Packages with checksums can't be read by versions without them.
+    pub fn checksums(mut self, enabled: bool) -> Self {
+        self.checksums = enabled;
+        self
+    }
+
+    /// Writes a package of `input_files` below `root`, see `write_package`.
+    pub fn write<W, T, P>(
+        &self,
+        root: P,
+        input_files: &[T],
+        writer: &mut W,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+        W: Write + WriteBytesExt,
+        T: AsRef<str> + Clone + Ord,
+    {
+        #[cfg(feature = "content_encoding")]
+        let compress: Option<Compress> = if self.gzip { Some(&gzip) } else { None };
+        #[cfg(not(feature = "content_encoding"))]
+        let compress = None;
+        write_package_data(root, input_files, writer, compress, self.checksums)
+    }
+
+    /// Writes a package of all files below `dir`, see `create_package_from_dir`.
+    pub fn create_from_dir<P, W>(&self, dir: P, writer: &mut W) -> Result<(), Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+        W: Write,
+    {
+        let (root, files) = package_paths(dir)?;
+        self.write(root, &files, writer)
+    }
+}
+
+/// Collects the paths of all files below `dir`, relative to it, along with its canonical path.
+fn package_paths<P: AsRef<Path>>(dir: P) -> Result<(PathBuf, Vec<String>), Box<dyn Error>> {
     let root = dir.as_ref().canonicalize()?;
     let mut files = Vec::new();
     for entry in WalkDir::new(&dir) {
@@ -361,7 +792,7 @@ where
         }
     }
 
-    write_package(root, &files, writer)
+    Ok((root, files))
 }
 
 #[cfg(test)]
@@ -386,11 +817,11 @@ mod tests {
         match package {
             Ok(p) => {
                 assert_eq!(p.files.len(), 5);
-                assert!(p.files.get("hello.txt").is_some());
-                assert!(p.files.get("inner/other.txt").is_some());
+                assert!(p.files.contains_key("hello.txt"));
+                assert!(p.files.contains_key("inner/other.txt"));
 
                 let hello_world = p.files.get("hello.txt").unwrap();
-                assert_eq!(hello_world.len, "Hello World!".as_bytes().len() as u64);
+                assert_eq!(hello_world.len, "Hello World!".len() as u64);
                 let mut hello_str = String::new();
                 p.open("hello.txt")
                     .unwrap()
@@ -399,7 +830,10 @@ mod tests {
                 assert_eq!(hello_str, "Hello World!");
 
                 assert!(p.is_dir("/"));
+                assert!(p.is_dir(""));
                 assert!(p.is_dir("/inner"));
+                assert!(p.is_dir("inner/"));
+                assert!(!p.is_dir("/inn"));
                 assert!(!p.is_dir("/not-there"));
                 assert!(!p.is_dir("/hello.txt"));
                 assert!(!p.is_dir("/inner/other.txt"));
@@ -416,10 +850,191 @@ mod tests {
                     _ => panic!("entry is not a file"),
                 };
             }
-            Err(e) => panic!(format!(
+            Err(e) => panic!(
                 "unable to read test.package, maybe you just need to re-run the test: {}",
                 e
-            )),
+            ),
+        }
+    }
+
+    #[test]
+    fn test_embedded_file_system_paths() {
+        let bytes = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/target/test.package"));
+        let fs = EmbeddedFileSystem::from_bytes(bytes).expect("unable to load test.package");
+
+        assert_eq!(
+            fs.paths(),
+            vec![
+                "hello.txt",
+                "inner/deeper/deep.txt",
+                "inner/other.txt",
+                "yyy.txt",
+                "zzz.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_walk() {
+        let bytes = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/target/test.package"));
+        let fs = EmbeddedFileSystem::from_bytes(bytes).expect("unable to load test.package");
+        let local = crate::fs::LocalFileSystem::new("testdata/assets");
+
+        let rt = rocket::tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let files = fs.walk("/").await.unwrap();
+            assert_eq!(files, fs.paths());
+            assert_eq!(files.len(), 5);
+            // The default implementation walks to the same result
+            assert_eq!(local.walk("").await.unwrap(), files);
+
+            let inner = vec!["inner/deeper/deep.txt", "inner/other.txt"];
+            assert_eq!(fs.walk("inner").await.unwrap(), inner);
+            assert_eq!(local.walk("/inner/").await.unwrap(), inner);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn test_mapped_package() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/target/test.package");
+        let fs = unsafe { EmbeddedFileSystem::map_file(path) }.expect("unable to map test.package");
+        assert!(fs.validate().is_ok());
+        assert_eq!(fs.paths().len(), 5);
+        assert!(fs.static_bytes("hello.txt").is_none());
+
+        let rt = rocket::tokio::runtime::Runtime::new().unwrap();
+        let (mut rest, all) = rt.block_on(async {
+            (
+                fs.open("hello.txt", Some(6)).await.unwrap(),
+                fs.open("hello.txt", None).await.unwrap(),
+            )
+        });
+
+        let mut body = String::new();
+        rest.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "World!");
+
+        let mut body = String::new();
+        Read::read_to_string(&mut Read::take(all, 5), &mut body).unwrap();
+        assert_eq!(body, "Hello");
+    }
+
+    #[test]
+    fn test_truncated_package() {
+        let bytes = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/target/test.package"));
+        let fs = EmbeddedFileSystem::from_bytes(&bytes[..bytes.len() - 4])
+            .expect("metadata is still intact");
+
+        assert!(fs.validate().is_err());
+        assert!(fs.package.open("zzz.txt").is_err());
+        assert!(fs.package.open("hello.txt").is_ok());
+
+        let fs = EmbeddedFileSystem::from_bytes(bytes).expect("unable to load test.package");
+        assert!(fs.validate().is_ok());
+    }
+
+    #[test]
+    fn test_package_pointing_past_data() {
+        fn package(len: u64, start: u64) -> &'static [u8] {
+            let path = b"a.txt";
+            let mut bytes = Vec::new();
+            bytes.write_u64::<BigEndian>(32 + path.len() as u64).unwrap();
+            bytes.write_u64::<BigEndian>(path.len() as u64).unwrap();
+            bytes.extend_from_slice(path);
+            bytes.write_i64::<BigEndian>(0).unwrap();
+            bytes.write_u64::<BigEndian>(len).unwrap();
+            bytes.write_u64::<BigEndian>(start).unwrap();
+            bytes.extend_from_slice(b"data");
+            Box::leak(bytes.into_boxed_slice())
+        }
+
+        let p = Package::from_bytes(package(4, 0)).unwrap();
+        assert!(p.open("a.txt").is_ok());
+
+        for (len, start) in &[(10, 0), (4, 1), (10, u64::MAX - 1)] {
+            let p = Package::from_bytes(package(*len, *start)).unwrap();
+            assert!(p.open("a.txt").is_err());
+        }
+    }
+
+    #[test]
+    fn test_validate_all() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/assets");
+        let mut bytes = Vec::new();
+        PackageBuilder::new()
+            .checksums(true)
+            .create_from_dir(dir, &mut bytes)
+            .expect("unable to create package");
+        let fs = EmbeddedFileSystem::from_vec(bytes.clone()).expect("valid package");
+        assert!(fs.validate_all().is_ok());
+
+        // hello.txt comes first, so its bytes start right after the metadata
+        let data_start = fs.package.data_start;
+        let mut tampered = bytes.clone();
+        tampered[data_start] ^= 0xff;
+        let fs = EmbeddedFileSystem::from_vec(tampered).expect("metadata is still intact");
+        // The bounds are fine, only the checksum tells
+        assert!(fs.validate().is_ok());
+        let err = fs.validate_all().expect_err("tampered package is rejected");
+        assert_eq!(err.to_string(), "corrupt files in the package: hello.txt");
+
+        let mut tampered = bytes[..bytes.len() - 4].to_vec();
+        tampered[data_start] ^= 0xff;
+        let fs = EmbeddedFileSystem::from_vec(tampered).expect("metadata is still intact");
+        let err = fs.validate_all().expect_err("tampered package is rejected");
+        assert_eq!(err.to_string(), "corrupt files in the package: hello.txt, zzz.txt");
+
+        // By default packages keep the layout without checksums, so only bounds are checked
+        let mut bytes = Vec::new();
+        create_package_from_dir(dir, &mut bytes).expect("unable to create package");
+        let fs = EmbeddedFileSystem::from_vec(bytes.clone()).expect("valid package");
+        let meta_len = Cursor::new(&bytes).read_u64::<BigEndian>().unwrap();
+        let paths_len: usize = fs.package.files.keys().map(String::len).sum();
+        assert_eq!(meta_len, (32 * fs.package.files.len() + paths_len) as u64);
+        assert!(fs.package.files.values().all(|file| file.checksum.is_none()));
+        let mut tampered = bytes.clone();
+        tampered[fs.package.data_start] ^= 0xff;
+        let fs = EmbeddedFileSystem::from_vec(tampered).expect("metadata is still intact");
+        assert!(fs.validate_all().is_ok());
+    }
+
+    #[cfg(feature = "content_encoding")]
+    #[test]
+    fn test_validate_all_gzip_variants() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/validate-gzip-package");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("app.js"), "console.log('hello');\n".repeat(50)).unwrap();
+        std::fs::write(dir.join("tiny.txt"), "tiny").unwrap();
+        let mut bytes = Vec::new();
+        create_package_from_dir_with_gzip(&dir, &mut bytes).expect("unable to create package");
+        let fs = EmbeddedFileSystem::from_vec(bytes.clone()).expect("valid package");
+        assert!(fs.validate_all().is_ok());
+
+        // The only variant is the one of app.js, at the very end. Flip a byte of its deflate
+        // stream, and one of the CRC32 in its trailer.
+        let (variant_len, _) = fs.package.files["app.js"].gzip.expect("app.js has a variant");
+        let variant_start = bytes.len() - variant_len as usize;
+        for tampered_at in [variant_start + variant_len as usize / 2, bytes.len() - 8] {
+            let mut tampered = bytes.clone();
+            tampered[tampered_at] ^= 0xff;
+            let fs = EmbeddedFileSystem::from_vec(tampered).expect("metadata is still intact");
+            assert!(fs.validate().is_ok());
+            let err = fs.validate_all().expect_err("tampered package is rejected");
+            assert_eq!(err.to_string(), "corrupt files in the package: app.js");
+        }
+    }
+
+    #[test]
+    fn test_package_with_huge_meta_len() {
+        for meta_len in &[u64::MAX, u64::MAX - 7, 1 << 40] {
+            let mut bytes = Vec::new();
+            bytes.write_u64::<BigEndian>(*meta_len).unwrap();
+            bytes.extend_from_slice(&[0; 64]);
+            let bytes: &'static [u8] = Box::leak(bytes.into_boxed_slice());
+
+            let err = Package::from_bytes(bytes).err().expect("package is rejected");
+            assert_eq!(err.to_string(), "package metadata exceeds the package");
         }
     }
 }
@@ -0,0 +1,176 @@
+use super::{Entry, FileMeta, FileSystem};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Wraps another FileSystem and serves all of its files at the top level by their name, so
+/// the backend's `foo/bar.js` is served as `bar.js`. The directories themselves aren't served,
+/// only the root lists every file.
+pub struct FlattenedFileSystem<T> {
+    inner: T,
+    files: HashMap<String, PathBuf>,
+}
+
+impl<T> FlattenedFileSystem<T>
+where
+    T: FileSystem + Send + Sync,
+{
+    /// Collects the files of `inner`, failing if two of them have the same name.
+    pub async fn new(inner: T) -> Result<FlattenedFileSystem<T>, Box<dyn Error>> {
+        let mut files: HashMap<String, PathBuf> = HashMap::new();
+        for path in inner.walk("").await? {
+            let path = PathBuf::from(path);
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            if let Some(other) = files.get(&name) {
+                return Err(Box::new(crate::Error::new(&format!(
+                    "{} and {} would both be served as {}",
+                    other.display(),
+                    path.display(),
+                    name
+                ))));
+            }
+            files.insert(name, path);
+        }
+
+        Ok(FlattenedFileSystem { inner, files })
+    }
+}
+
+impl<T> FlattenedFileSystem<T> {
+    fn is_root(path: &Path) -> bool {
+        path.to_str().is_some_and(|path| path.trim_matches('/').is_empty())
+    }
+
+    fn map(&self, path: &Path) -> Option<&PathBuf> {
+        let name = path.to_str()?.trim_start_matches('/');
+        self.files.get(name)
+    }
+
+    fn missing() -> Box<dyn Error> {
+        Box::new(crate::Error::new("file does not exist"))
+    }
+}
+
+#[rocket::async_trait]
+impl<T> FileSystem for FlattenedFileSystem<T>
+where
+    T: FileSystem + Send + Sync,
+{
+    type Read = <T as FileSystem>::Read;
+
+    async fn is_file<P>(&self, path: P) -> bool
+        where P: AsRef<Path> + Send
+    {
+        match self.map(path.as_ref()) {
+            Some(path) => self.inner.is_file(path).await,
+            None => false,
+        }
+    }
+
+    async fn is_dir<P>(&self, path: P) -> bool
+        where P: AsRef<Path> + Send
+    {
+        Self::is_root(path.as_ref())
+    }
+
+    async fn last_modified<P>(&self, path: P) -> Result<SystemTime, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        match self.map(path.as_ref()) {
+            Some(path) => self.inner.last_modified(path).await,
+            None => Err(Self::missing()),
+        }
+    }
+
+    async fn size<P>(&self, path: P) -> Result<u64, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        match self.map(path.as_ref()) {
+            Some(path) => self.inner.size(path).await,
+            None => Err(Self::missing()),
+        }
+    }
+
+    async fn open<P>(
+        &self,
+        path: P,
+        start: Option<u64>,
+    ) -> Result<<Self as FileSystem>::Read, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        match self.map(path.as_ref()) {
+            Some(path) => self.inner.open(path, start).await,
+            None => Err(Self::missing()),
+        }
+    }
+
+    async fn path_valid<P>(&self, path: P) -> bool
+        where P: AsRef<Path> + Send
+    {
+        // Only mapped files are ever looked up, any other path simply doesn't exist
+        match self.map(path.as_ref()) {
+            Some(path) => self.inner.path_valid(path).await,
+            None => true,
+        }
+    }
+
+    async fn entries<P>(&self, path: P) -> Result<Vec<Entry>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        if !Self::is_root(path.as_ref()) {
+            return Err(Box::new(crate::Error::new("directory does not exist")));
+        }
+
+        let mut entries = Vec::with_capacity(self.files.len());
+        for (name, path) in self.files.iter() {
+            // Files which went away since are left out
+            if let Ok(Some(FileMeta::File(size, modified))) = self.inner.stat(path).await {
+                entries.push(Entry::File(name.to_string(), size, modified));
+            }
+        }
+        entries.sort_by(|a, b| a.name().cmp(b.name()));
+        Ok(entries)
+    }
+
+    fn supports_ranges(&self) -> bool {
+        self.inner.supports_ranges()
+    }
+
+    fn verify(&self) -> Result<(), Box<dyn Error>> {
+        self.inner.verify()
+    }
+
+    async fn stat<P>(&self, path: P) -> Result<Option<FileMeta>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        if Self::is_root(path.as_ref()) {
+            return Ok(Some(FileMeta::Dir));
+        }
+        match self.map(path.as_ref()) {
+            Some(path) => self.inner.stat(path).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn walk<P>(&self, path: P) -> Result<Vec<String>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        if !Self::is_root(path.as_ref()) {
+            return Err(Box::new(crate::Error::new("directory does not exist")));
+        }
+
+        let mut files: Vec<String> = self.files.keys().cloned().collect();
+        files.sort();
+        Ok(files)
+    }
+
+    fn static_bytes<P>(&self, path: P) -> Option<&'static [u8]>
+        where P: AsRef<Path>
+    {
+        self.inner.static_bytes(self.map(path.as_ref())?)
+    }
+}
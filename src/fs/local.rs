@@ -1,15 +1,20 @@
-use super::{Entry, FileSystem};
+use super::{Entry, FileMeta, FileSystem};
+use std::collections::HashSet;
 use std::error::Error;
 use std::fs;
-use std::io::SeekFrom;
-use std::path::{Path, PathBuf};
+use std::io::{self, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use std::time::SystemTime;
 use tokio::fs::File;
 use tokio::io::AsyncSeekExt;
 
 /// Implements the FileSystem trait to handle a local directory.
+///
+/// Several directories can be served as one with `with_roots`, e.g. a development asset
+/// directory taking precedence over the built ones.
 pub struct LocalFileSystem {
-    path: PathBuf,
+    roots: RwLock<Arc<Vec<PathBuf>>>,
 }
 
 impl LocalFileSystem
@@ -17,10 +22,51 @@ impl LocalFileSystem
     pub fn new<P>(path: P) -> LocalFileSystem
         where P: AsRef<Path> + Send
     {
+        LocalFileSystem::with_roots(vec![path.as_ref().to_owned()])
+    }
+
+    /// Serves from all of `roots`, looking up every path in the first root which has
+    /// anything at it. Listings merge the directories of all roots, with files of earlier
+    /// roots hiding those of the same name in later ones.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `roots` is empty.
+    pub fn with_roots(roots: Vec<PathBuf>) -> LocalFileSystem {
+        assert!(!roots.is_empty(), "a LocalFileSystem needs at least one root");
         LocalFileSystem {
-            path: path.as_ref().to_owned(),
+            roots: RwLock::new(Arc::new(roots)),
         }
     }
+
+    /// Serves from `path` from now on, e.g. to switch to a freshly deployed directory. Share
+    /// the filesystem through an `Arc` to call this while it's attached.
+    pub fn set_root<P>(&self, path: P)
+        where P: AsRef<Path>
+    {
+        *self.roots.write().unwrap() = Arc::new(vec![path.as_ref().to_owned()]);
+    }
+
+    /// The directory currently served from, or the first one of several roots.
+    pub fn root(&self) -> PathBuf {
+        self.roots()[0].clone()
+    }
+
+    /// All directories currently served from, in the order they're looked up in.
+    pub fn roots(&self) -> Arc<Vec<PathBuf>> {
+        Arc::clone(&self.roots.read().unwrap())
+    }
+
+    /// Joins `path` onto the first root which has anything at it, or onto the first root if
+    /// none does.
+    fn resolve<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let roots = self.roots();
+        roots
+            .iter()
+            .map(|root| root.join(path.as_ref()))
+            .find(|full| full.symlink_metadata().is_ok())
+            .unwrap_or_else(|| roots[0].join(path.as_ref()))
+    }
 }
 
 #[rocket::async_trait]
@@ -30,26 +76,26 @@ impl FileSystem for LocalFileSystem {
     async fn is_file<P>(&self, path: P) -> bool
         where P: AsRef<Path> + Send
     {
-        self.path.join(path).is_file()
+        self.resolve(path).is_file()
     }
 
     async fn is_dir<P>(&self, path: P) -> bool
         where P: AsRef<Path> + Send
     {
-        self.path.join(path).is_dir()
+        self.resolve(path).is_dir()
     }
 
     async fn last_modified<P>(&self, path: P) -> Result<SystemTime, Box<dyn Error>>
         where P: AsRef<Path> + Send
     {
-        let modified = self.path.join(path).metadata()?.modified()?;
+        let modified = self.resolve(path).metadata()?.modified()?;
         Ok(modified)
     }
 
     async fn size<P>(&self, path: P) -> Result<u64, Box<dyn Error>>
         where P: AsRef<Path> + Send
     {
-        let len = self.path.join(path).metadata()?.len();
+        let len = self.resolve(path).metadata()?.len();
         Ok(len)
     }
 
@@ -60,8 +106,9 @@ impl FileSystem for LocalFileSystem {
     ) -> Result<<Self as FileSystem>::Read, Box<dyn Error>>
         where P: AsRef<Path> + Send
     {
-        let mut f = File::open(self.path.join(path)).await?;
-        if let Some(start) = start {
+        let mut f = File::open(self.resolve(path)).await?;
+        // A fresh file already is at the start, seeking there would only cost a syscall
+        if let Some(start) = start.filter(|start| *start > 0) {
             f.seek(SeekFrom::Start(start)).await?;
         }
         Ok(f)
@@ -70,29 +117,102 @@ impl FileSystem for LocalFileSystem {
     async fn path_valid<P>(&self, path: P) -> bool
         where P: AsRef<Path> + Send
     {
-        let path = self.path.join(path);
-        path.starts_with(&self.path)
+        // Joining doesn't resolve `..`, so those would lead out of the root unnoticed
+        let path = path.as_ref();
+        if path.components().any(|c| c == Component::ParentDir) {
+            return false;
+        }
+        let root = self.root();
+        root.join(path).starts_with(root.as_path())
     }
 
     async fn entries<P>(&self, path: P) -> Result<Vec<Entry>, Box<dyn Error>>
         where P: AsRef<Path> + Send
     {
-        let dir = fs::read_dir(self.path.join(path.as_ref()))?;
-        let mut entries = Vec::new();
-        for f in dir {
-            let f = f?;
-            let meta = f.metadata()?;
-            let filename = f.file_name().to_str().unwrap().to_string();
-
-            if meta.is_file() {
-                let size = meta.len();
-                let modified = meta.modified()?;
-                entries.push(Entry::File(filename, size, modified));
-            } else if meta.is_dir() {
-                entries.push(Entry::Dir(filename));
+        let roots = self.roots();
+        let dirs: Vec<PathBuf> = roots
+            .iter()
+            .map(|root| root.join(path.as_ref()))
+            .filter(|dir| dir.is_dir())
+            .collect();
+        if dirs.is_empty() {
+            // Let the first root tell why there is no directory
+            return Ok(read_entries(&roots[0].join(path.as_ref()))?);
+        }
+
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut names = HashSet::new();
+        for dir in dirs {
+            for entry in read_entries(&dir)? {
+                if names.insert(entry.name().to_string()) {
+                    entries.push(entry);
+                }
             }
-            // TODO: Are there other possibilities? How are symlinks noted?
         }
         Ok(entries)
     }
+
+    async fn stat<P>(&self, path: P) -> Result<Option<FileMeta>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        // Like is_file and is_dir, anything we can't stat counts as not there
+        let meta = match self.resolve(path).metadata() {
+            Ok(meta) => meta,
+            Err(_) => return Ok(None),
+        };
+        if meta.is_file() {
+            Ok(Some(FileMeta::File(meta.len(), meta.modified()?)))
+        } else if meta.is_dir() {
+            Ok(Some(FileMeta::Dir))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Fails unless every root is a directory which can be read.
+    fn verify(&self) -> Result<(), Box<dyn Error>> {
+        for root in self.roots().iter() {
+            // An empty root serves the working directory, as paths are joined onto it
+            let dir = if root.as_os_str().is_empty() { Path::new(".") } else { root };
+            if let Err(err) = fs::read_dir(dir) {
+                let description = format!("can't serve {}: {}", root.display(), err);
+                return Err(Box::new(crate::Error::new(&description)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the entries of a single directory.
+fn read_entries(path: &Path) -> io::Result<Vec<Entry>> {
+    let dir = fs::read_dir(path)?;
+    let mut entries = Vec::new();
+    for f in dir {
+        // A single entry we can't read or name shouldn't hide the rest of the directory
+        let f = match f {
+            Ok(f) => f,
+            Err(_) => continue,
+        };
+        let meta = match f.metadata() {
+            Ok(meta) => meta,
+            Err(_) => continue,
+        };
+        let filename = match f.file_name().to_str() {
+            Some(filename) => filename.to_string(),
+            None => continue,
+        };
+
+        if meta.is_file() {
+            let size = meta.len();
+            let modified = match meta.modified() {
+                Ok(modified) => modified,
+                Err(_) => continue,
+            };
+            entries.push(Entry::File(filename, size, modified));
+        } else if meta.is_dir() {
+            entries.push(Entry::Dir(filename));
+        }
+        // TODO: Are there other possibilities? How are symlinks noted?
+    }
+    Ok(entries)
 }
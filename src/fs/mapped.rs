@@ -0,0 +1,124 @@
+use super::{Entry, FileMeta, FileSystem};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Wraps another FileSystem and rewrites paths under `from` to be under `to` instead, e.g. to
+/// serve the backend's `dist/public/` for requests to `public/`. Other paths are passed on as
+/// they are.
+pub struct MappedFileSystem<T> {
+    inner: T,
+    from: PathBuf,
+    to: PathBuf,
+}
+
+impl<T> MappedFileSystem<T>
+{
+    pub fn new<F, P>(inner: T, from: F, to: P) -> MappedFileSystem<T>
+        where F: AsRef<Path>, P: AsRef<Path>
+    {
+        MappedFileSystem {
+            inner,
+            from: from.as_ref().to_owned(),
+            to: to.as_ref().to_owned(),
+        }
+    }
+
+    fn map(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.from) {
+            Ok(rest) => self.to.join(rest),
+            Err(_) => path.to_owned(),
+        }
+    }
+
+    /// Turns a path of the inner FileSystem back into the one it's served as.
+    fn unmap(&self, path: String) -> String {
+        match Path::new(&path).strip_prefix(&self.to) {
+            Ok(rest) => self.from.join(rest).to_string_lossy().into_owned(),
+            Err(_) => path,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<T> FileSystem for MappedFileSystem<T>
+where
+    T: FileSystem + Send + Sync,
+{
+    type Read = <T as FileSystem>::Read;
+
+    async fn is_file<P>(&self, path: P) -> bool
+        where P: AsRef<Path> + Send
+    {
+        self.inner.is_file(self.map(path.as_ref())).await
+    }
+
+    async fn is_dir<P>(&self, path: P) -> bool
+        where P: AsRef<Path> + Send
+    {
+        self.inner.is_dir(self.map(path.as_ref())).await
+    }
+
+    async fn last_modified<P>(&self, path: P) -> Result<SystemTime, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        self.inner.last_modified(self.map(path.as_ref())).await
+    }
+
+    async fn size<P>(&self, path: P) -> Result<u64, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        self.inner.size(self.map(path.as_ref())).await
+    }
+
+    async fn open<P>(
+        &self,
+        path: P,
+        start: Option<u64>,
+    ) -> Result<<Self as FileSystem>::Read, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        self.inner.open(self.map(path.as_ref()), start).await
+    }
+
+    async fn path_valid<P>(&self, path: P) -> bool
+        where P: AsRef<Path> + Send
+    {
+        self.inner.path_valid(self.map(path.as_ref())).await
+    }
+
+    async fn entries<P>(&self, path: P) -> Result<Vec<Entry>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        self.inner.entries(self.map(path.as_ref())).await
+    }
+
+    fn supports_ranges(&self) -> bool {
+        self.inner.supports_ranges()
+    }
+
+    fn verify(&self) -> Result<(), Box<dyn Error>> {
+        self.inner.verify()
+    }
+
+    async fn stat<P>(&self, path: P) -> Result<Option<FileMeta>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        self.inner.stat(self.map(path.as_ref())).await
+    }
+
+    async fn walk<P>(&self, path: P) -> Result<Vec<String>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        let files = self.inner.walk(self.map(path.as_ref())).await?;
+        let mut files: Vec<String> = files.into_iter().map(|file| self.unmap(file)).collect();
+        files.sort();
+        Ok(files)
+    }
+
+    fn static_bytes<P>(&self, path: P) -> Option<&'static [u8]>
+        where P: AsRef<Path>
+    {
+        self.inner.static_bytes(self.map(path.as_ref()))
+    }
+}
@@ -3,49 +3,97 @@
 use chrono::prelude::*;
 use std::error::Error;
 use std::path::Path;
+use std::sync::Arc;
 use std::time::SystemTime;
 use rocket::tokio::io::AsyncRead;
 
 mod embedded;
+mod flattened;
 mod local;
+mod mapped;
+#[cfg(feature = "zip_archive")]
+mod zip_archive;
 
 pub use self::embedded::create_package_from_dir;
+#[cfg(feature = "content_encoding")]
+pub use self::embedded::create_package_from_dir_with_gzip;
 pub use self::embedded::write_package;
+#[cfg(feature = "content_encoding")]
+pub use self::embedded::write_package_with_gzip;
+pub use self::embedded::EmbeddedFile;
 pub use self::embedded::EmbeddedFileSystem;
+pub use self::embedded::PackageBuilder;
+pub use self::flattened::FlattenedFileSystem;
 pub use self::local::LocalFileSystem;
+pub use self::mapped::MappedFileSystem;
+#[cfg(feature = "zip_archive")]
+pub use self::zip_archive::ZipFileSystem;
 
 pub enum Entry {
     File(String, u64, SystemTime),
     Dir(String),
 }
 
+impl Entry {
+    /// Returns the name of the file or directory.
+    pub fn name(&self) -> &str {
+        match *self {
+            Entry::File(ref name, _, _) => name,
+            Entry::Dir(ref name) => name,
+        }
+    }
+}
+
+/// What `FileSystem::stat` knows about a path: a file with its size and modification date,
+/// or a directory.
+#[derive(Debug, PartialEq)]
+pub enum FileMeta {
+    File(u64, SystemTime),
+    Dir,
+}
+
 #[derive(Serialize)]
 pub struct TemplateEntry {
     name: String,
+    /// The name percent-encoded for links, see `encode_segment`.
+    href: String,
     size: u64,
     last_modified: String,
+    /// The modification date as RFC 3339, e.g. `2020-01-31T12:00:00Z`, for sorting.
+    iso_modified: String,
     is_file: bool,
 }
 
+impl TemplateEntry {
+    pub(crate) fn href(&self) -> &str {
+        &self.href
+    }
+}
+
 impl<'a> From<&'a Entry> for TemplateEntry {
     fn from(e: &'a Entry) -> Self {
-        match e.clone() {
+        match e {
             Entry::File(name, size, last_modified) => {
                 let last_modified: DateTime<Utc> = DateTime::from(*last_modified);
+                let iso_modified = last_modified.to_rfc3339_opts(SecondsFormat::Secs, true);
                 let last_modified = last_modified
                     .format(crate::LAST_MODIFIED_DATE_FORMAT)
                     .to_string();
                 TemplateEntry {
                     name: name.to_string(),
+                    href: crate::encode_segment(name),
                     size: *size,
                     last_modified,
+                    iso_modified,
                     is_file: true,
                 }
             }
             Entry::Dir(name) => TemplateEntry {
                 name: name.to_string(),
+                href: crate::encode_segment(name),
                 size: 0,
                 last_modified: String::new(),
+                iso_modified: String::new(),
                 is_file: false,
             },
         }
@@ -65,6 +113,12 @@ pub trait FileSystem {
         where P: AsRef<Path> + Send;
     async fn size<P>(&self, path: P) -> Result<u64, Box<dyn Error>>
         where P: AsRef<Path> + Send;
+    /// Opens the file at `path`, reading from byte `start` on if given. Full responses get no
+    /// `start`, so backends don't need to seek for them.
+    ///
+    /// Backends which can't seek must return false from `supports_ranges`; they're only ever
+    /// opened without a `start` and should return an error if they get one anyway rather
+    /// than reading from the beginning.
     async fn open<P>(
         &self,
         path: P,
@@ -75,4 +129,157 @@ pub trait FileSystem {
         where P: AsRef<Path> + Send;
     async fn entries<P>(&self, path: P) -> Result<Vec<Entry>, Box<dyn Error>>
         where P: AsRef<Path> + Send;
+
+    /// Whether `open` can start reading at an offset, so byte ranges can be served.
+    fn supports_ranges(&self) -> bool {
+        true
+    }
+
+    /// Checks that there's something to serve at all, e.g. that a served directory exists.
+    /// `StaticFileServer::new` fails with the error, so misconfigurations surface at startup
+    /// rather than as every request being not found.
+    fn verify(&self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Returns the whole contents of the file at `path` if they're always in memory, so they
+    /// can be served without streaming.
+    fn static_bytes<P>(&self, _path: P) -> Option<&'static [u8]>
+        where P: AsRef<Path>
+    {
+        None
+    }
+
+    /// Looks up whether `path` is a file or a directory, and a file's size and modification
+    /// date, in one call. Returns `None` if there is nothing at `path`.
+    ///
+    /// The default asks `is_file`, `is_dir`, `size` and `last_modified` one after another, so
+    /// backends which can get all of it at once, e.g. over the network, should override it.
+    async fn stat<P>(&self, path: P) -> Result<Option<FileMeta>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        let path = path.as_ref();
+        if self.is_file(path).await {
+            let size = self.size(path).await?;
+            let modified = self.last_modified(path).await?;
+            Ok(Some(FileMeta::File(size, modified)))
+        } else if self.is_dir(path).await {
+            Ok(Some(FileMeta::Dir))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns the paths of all files below the directory at `path` recursively, relative to
+    /// the root of the filesystem and sorted, e.g. to generate a sitemap.
+    ///
+    /// The default walks the directories with `entries`.
+    async fn walk<P>(&self, path: P) -> Result<Vec<String>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        let root = match path.as_ref().to_str() {
+            Some(root) => root.trim_matches('/').to_string(),
+            None => return Err(Box::new(crate::Error::new("path is not valid UTF-8"))),
+        };
+
+        let mut dirs = vec![root];
+        let mut files = Vec::new();
+        while let Some(dir) = dirs.pop() {
+            for entry in self.entries(&dir).await? {
+                let path = if dir.is_empty() {
+                    entry.name().to_string()
+                } else {
+                    format!("{}/{}", dir, entry.name())
+                };
+                match entry {
+                    Entry::Dir(_) => dirs.push(path),
+                    Entry::File(..) => files.push(path),
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// Allows sharing one filesystem between several `StaticFileServer`s, e.g. to serve the same
+/// embedded package under multiple prefixes without duplicating it.
+#[rocket::async_trait]
+impl<T> FileSystem for Arc<T>
+where
+    T: FileSystem + Send + Sync,
+{
+    type Read = <T as FileSystem>::Read;
+
+    async fn is_file<P>(&self, path: P) -> bool
+        where P: AsRef<Path> + Send
+    {
+        (**self).is_file(path).await
+    }
+
+    async fn is_dir<P>(&self, path: P) -> bool
+        where P: AsRef<Path> + Send
+    {
+        (**self).is_dir(path).await
+    }
+
+    async fn last_modified<P>(&self, path: P) -> Result<SystemTime, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        (**self).last_modified(path).await
+    }
+
+    async fn size<P>(&self, path: P) -> Result<u64, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        (**self).size(path).await
+    }
+
+    async fn open<P>(
+        &self,
+        path: P,
+        start: Option<u64>,
+    ) -> Result<<Self as FileSystem>::Read, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        (**self).open(path, start).await
+    }
+
+    async fn path_valid<P>(&self, path: P) -> bool
+        where P: AsRef<Path> + Send
+    {
+        (**self).path_valid(path).await
+    }
+
+    async fn entries<P>(&self, path: P) -> Result<Vec<Entry>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        (**self).entries(path).await
+    }
+
+    fn supports_ranges(&self) -> bool {
+        (**self).supports_ranges()
+    }
+
+    fn verify(&self) -> Result<(), Box<dyn Error>> {
+        (**self).verify()
+    }
+
+    async fn stat<P>(&self, path: P) -> Result<Option<FileMeta>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        (**self).stat(path).await
+    }
+
+    async fn walk<P>(&self, path: P) -> Result<Vec<String>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        (**self).walk(path).await
+    }
+
+    fn static_bytes<P>(&self, path: P) -> Option<&'static [u8]>
+        where P: AsRef<Path>
+    {
+        (**self).static_bytes(path)
+    }
 }
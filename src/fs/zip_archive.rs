@@ -0,0 +1,220 @@
+use super::{Entry, FileMeta, FileSystem};
+use chrono::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::{Component, Path};
+use std::sync::Arc;
+use std::time::SystemTime;
+use zip::ZipArchive;
+
+/// The most bytes reserved upfront for a file being decompressed. Sizes come from the archive
+/// and may be made up, so larger files grow their buffer as they're actually read.
+const MAX_PREALLOCATION: u64 = 1 << 20;
+
+/// Serves the files of a zip archive, which is read into memory once.
+///
+/// Files are decompressed completely when opened, also for ranges, which are cut out of the
+/// decompressed bytes. This suits archives of web assets, not ones of huge files.
+///
+/// Requires the `zip_archive` feature.
+pub struct ZipFileSystem {
+    archive: ZipArchive<Cursor<Arc<[u8]>>>,
+    files: HashMap<String, ZipEntry>,
+    dirs: HashSet<String>,
+}
+
+struct ZipEntry {
+    index: usize,
+    size: u64,
+    last_modified: SystemTime,
+}
+
+impl ZipFileSystem {
+    /// Indexes the archive in `bytes`, failing if it isn't a valid zip archive.
+    pub fn from_vec(bytes: Vec<u8>) -> Result<Self, Box<dyn Error>> {
+        let mut archive = ZipArchive::new(Cursor::new(Arc::from(bytes)))?;
+
+        let mut files = HashMap::new();
+        let mut dirs = HashSet::new();
+        dirs.insert(String::new());
+        for index in 0..archive.len() {
+            let file = archive.by_index_raw(index)?;
+            // Entries escaping the archive, like `../x`, are left out
+            let name = match file.enclosed_name().and_then(|name| normalize(&name)) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            let mut parent = name.as_str();
+            while let Some((dir, _)) = parent.rsplit_once('/') {
+                dirs.insert(dir.to_string());
+                parent = dir;
+            }
+            if file.is_dir() {
+                dirs.insert(name);
+                continue;
+            }
+
+            // Zip dates don't have a time zone, they're taken as UTC
+            let last_modified = file
+                .last_modified()
+                .and_then(|t| {
+                    NaiveDate::from_ymd_opt(t.year().into(), t.month().into(), t.day().into())?
+                        .and_hms_opt(t.hour().into(), t.minute().into(), t.second().into())
+                })
+                .map(|t| t.and_utc().into())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            let entry = ZipEntry {
+                index,
+                size: file.size(),
+                last_modified,
+            };
+            files.insert(name, entry);
+        }
+
+        Ok(ZipFileSystem {
+            archive,
+            files,
+            dirs,
+        })
+    }
+
+    /// Reads and indexes the zip archive at `path`, see `from_vec`.
+    pub fn open_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        ZipFileSystem::from_vec(bytes)
+    }
+
+    fn file(&self, path: &Path) -> Option<&ZipEntry> {
+        self.files.get(&normalize(path)?)
+    }
+
+    fn missing() -> Box<dyn Error> {
+        Box::new(crate::Error::new("file does not exist"))
+    }
+}
+
+/// Turns a path into the form files are indexed by, e.g. `dir/file.txt`, or `None` if it
+/// leaves the root.
+fn normalize(path: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+#[rocket::async_trait]
+impl FileSystem for ZipFileSystem {
+    type Read = Cursor<Vec<u8>>;
+
+    async fn is_file<P>(&self, path: P) -> bool
+        where P: AsRef<Path> + Send
+    {
+        self.file(path.as_ref()).is_some()
+    }
+
+    async fn is_dir<P>(&self, path: P) -> bool
+        where P: AsRef<Path> + Send
+    {
+        normalize(path.as_ref()).is_some_and(|path| self.dirs.contains(&path))
+    }
+
+    async fn last_modified<P>(&self, path: P) -> Result<SystemTime, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        match self.file(path.as_ref()) {
+            Some(file) => Ok(file.last_modified),
+            None => Err(Self::missing()),
+        }
+    }
+
+    async fn size<P>(&self, path: P) -> Result<u64, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        match self.file(path.as_ref()) {
+            Some(file) => Ok(file.size),
+            None => Err(Self::missing()),
+        }
+    }
+
+    async fn open<P>(
+        &self,
+        path: P,
+        start: Option<u64>,
+    ) -> Result<<Self as FileSystem>::Read, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        let file = self.file(path.as_ref()).ok_or_else(Self::missing)?;
+        // The archive only shares the bytes and index, each open reads on its own cursor
+        let mut archive = self.archive.clone();
+        let mut reader = archive.by_index(file.index)?;
+        let mut bytes = Vec::with_capacity(file.size.min(MAX_PREALLOCATION) as usize);
+        reader.read_to_end(&mut bytes)?;
+
+        let mut cursor = Cursor::new(bytes);
+        if let Some(start) = start {
+            cursor.set_position(start);
+        }
+        Ok(cursor)
+    }
+
+    async fn path_valid<P>(&self, path: P) -> bool
+        where P: AsRef<Path> + Send
+    {
+        normalize(path.as_ref()).is_some()
+    }
+
+    async fn entries<P>(&self, path: P) -> Result<Vec<Entry>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        let dir = match normalize(path.as_ref()) {
+            Some(dir) if self.dirs.contains(&dir) => dir,
+            _ => return Err(Box::new(crate::Error::new("directory does not exist"))),
+        };
+        // The name of a direct child of `dir`, if `path` is one
+        let child = |path: &'_ str| -> Option<String> {
+            let name = if dir.is_empty() {
+                path
+            } else {
+                path.strip_prefix(dir.as_str())?.strip_prefix('/')?
+            };
+            Some(name.to_string()).filter(|name| !name.is_empty() && !name.contains('/'))
+        };
+
+        let mut entries: Vec<Entry> = self
+            .dirs
+            .iter()
+            .filter_map(|path| child(path))
+            .map(Entry::Dir)
+            .chain(self.files.iter().filter_map(|(path, file)| {
+                child(path).map(|name| Entry::File(name, file.size, file.last_modified))
+            }))
+            .collect();
+        entries.sort_by(|a, b| a.name().cmp(b.name()));
+        Ok(entries)
+    }
+
+    async fn stat<P>(&self, path: P) -> Result<Option<FileMeta>, Box<dyn Error>>
+        where P: AsRef<Path> + Send
+    {
+        let path = match normalize(path.as_ref()) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        if let Some(file) = self.files.get(&path) {
+            Ok(Some(FileMeta::File(file.size, file.last_modified)))
+        } else if self.dirs.contains(&path) {
+            Ok(Some(FileMeta::Dir))
+        } else {
+            Ok(None)
+        }
+    }
+}
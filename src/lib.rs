@@ -6,9 +6,6 @@
 //! This example works for sharing the src folder of your app.
 //!
 //! ```rust,no_run
-//! #![feature(plugin)]
-//! #![plugin(rocket_codegen)]
-//!
 //! #[macro_use]
 //! extern crate rocket;
 //! extern crate rocket_static_fs;
@@ -20,65 +17,130 @@
 //!     "Hello, world!"
 //! }
 //!
-//! fn main() {
-//! let fs = fs::LocalFileSystem::new("src");
+//! #[launch]
+//! fn rocket() -> _ {
+//!     let fs = fs::LocalFileSystem::new("src");
 //!     let options = OptionsBuilder::new().prefix("/src").into();
-//!     rocket::ignite()
+//!     rocket::build()
 //!         .attach(StaticFileServer::new(fs, options).unwrap())
 //!         .mount("/", routes![index])
-//!         .launch();
 //! }
 //! ```
 
 extern crate chrono;
-#[cfg(target = "content_encoding")]
-extern crate flate2;
+#[cfg(feature = "content_encoding")]
+extern crate async_compression;
 extern crate mime_guess;
 extern crate regex;
 extern crate rocket;
-#[macro_use]
-extern crate lazy_static;
 extern crate byteorder;
+extern crate crc32fast;
 extern crate handlebars;
 extern crate walkdir;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde;
 
+mod archive;
+mod checksum;
 pub mod fs;
+mod limit;
+mod listing;
+mod metering;
 mod options;
+mod range;
+mod throttle;
+mod timing;
 
 pub use options::*;
 
+use archive::{ArchiveEntry, ArchiveFormat, ArchiveStream, OpenEntry};
+use checksum::Checksum;
 use chrono::prelude::*;
-#[cfg(target = "content_encoding")]
-use flate2::{read::DeflateEncoder, read::GzEncoder, Compression};
-use fs::{FileSystem, TemplateEntry};
+#[cfg(feature = "content_encoding")]
+use async_compression::tokio::bufread::{DeflateEncoder, GzipDecoder, GzipEncoder};
+use fs::{Entry, FileMeta, FileSystem, TemplateEntry};
 use handlebars::Handlebars;
-use regex::Regex;
 use rocket::fairing::{Fairing, Info, Kind};
 use rocket::http::Header;
+use rocket::http::RawStr;
 use rocket::http::Method;
 use rocket::http::Status;
-use rocket::{Request, Response};
+use rocket::response::Body;
+use rocket::{Data, Request, Response};
 use std::error::Error as StdError;
 use std::fmt;
-use std::io::Cursor;
+use std::io::{self, Cursor};
 use std::path::Path;
-use std::str::FromStr;
-use tokio::io::AsyncReadExt;
-
-lazy_static! {
-    static ref RANGE_HEADER_REGEX: Regex = Regex::new(r#"(.*?)=(\d+)-(\d+)"#).unwrap();
-    static ref RANGE_HEADER_NO_END_REGEX: Regex = Regex::new(r#"(.*?)=(\d+)-"#).unwrap();
-}
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use limit::PermitReader;
+use listing::{listing_templates, ListingStream};
+use metering::CountingReader;
+use range::Range;
+use throttle::ThrottledReader;
+use timing::TimedReader;
+#[cfg(feature = "content_encoding")]
+use tokio::io::{AsyncBufRead, BufReader};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, TryAcquireError};
 
 const LAST_MODIFIED_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
 
+/// Content types of modern extensions which browsers are strict about, taking precedence over
+/// whatever mime_guess thinks of them.
+const MODERN_MIME_TYPES: &[(&str, &str)] = &[
+    ("mjs", "text/javascript"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("woff2", "font/woff2"),
+    ("wasm", "application/wasm"),
+    ("webmanifest", "application/manifest+json"),
+];
+
 #[derive(Serialize)]
 struct DirectoryListingContext {
     directory: String,
+    /// Prepended to entry links, empty for links relative to the listed directory.
+    base: String,
+    title: Option<String>,
+    breadcrumbs: Vec<Breadcrumb>,
+    filter: Option<String>,
     entries: Vec<TemplateEntry>,
+    truncated: bool,
+    /// Whether the directory has no entries to list at all, regardless of the filter.
+    empty: bool,
+}
+
+/// What the RSS feed of a listing is rendered with, see `OptionsBuilder::listing_feed`.
+#[derive(Serialize)]
+struct FeedContext {
+    directory: String,
+    title: Option<String>,
+    /// The URL of the listed directory.
+    url: String,
+    entries: Vec<FeedEntry>,
+}
+
+/// A file in the RSS feed of a listing, along with the URL it's at.
+#[derive(Serialize)]
+struct FeedEntry {
+    #[serde(flatten)]
+    entry: TemplateEntry,
+    url: String,
+}
+
+/// A directory listing to send, rendered already or as it's read.
+enum Listing {
+    Rendered(String),
+    Streamed(Box<ListingStream>),
+}
+
+/// A link to the listed directory or one of its parents.
+#[derive(Serialize)]
+struct Breadcrumb {
+    name: String,
+    href: String,
 }
 
 #[derive(Debug)]
@@ -106,55 +168,239 @@ impl fmt::Display for Error {
     }
 }
 
-/// Represents a `Range` header.
+/// The method a request was made with.
 ///
-/// Implements FromStr for convenience.
-struct Range {
-    unit: String,
-    start: u64,
-    end: Option<u64>,
+/// Rocket answers `HEAD` requests without a matching route by routing them as `GET`, so by the
+/// time responses are handled the method has changed already. It's remembered here before.
+struct RequestMethod(Method);
+
+/// Checks whether the request's `Accept-Encoding` header accepts `encoding`, which it doesn't
+/// with a quality of 0 like `gzip;q=0`.
+fn accepts_encoding(request: &Request<'_>, encoding: &str) -> bool {
+    match request.headers().get_one("Accept-Encoding") {
+        Some(encodings) => encoding_quality(encodings, encoding) > 0.0,
+        None => false,
+    }
+}
+
+/// Splits an element of an `Accept-*` header like `fr;q=0.8` into its value and quality.
+fn quality_item(item: &str) -> (&str, f32) {
+    let mut params = item.split(';');
+    let name = params.next().unwrap_or("").trim();
+    let quality = match params.find_map(|param| param.trim().strip_prefix("q=")) {
+        Some(quality) => quality.trim().parse().unwrap_or(0.0),
+        None => 1.0,
+    };
+    (name, quality)
 }
 
-impl Range {
-    fn len(&self) -> Option<u64> {
-        match self.end {
-            Some(end) => Some(end - self.start + 1),
-            None => None,
+/// Returns the quality value an `Accept-Encoding` header gives `encoding`, 0 if it isn't
+/// accepted. A `*` stands in for any encoding which isn't listed.
+fn encoding_quality(accept_encoding: &str, encoding: &str) -> f32 {
+    let mut wildcard = 0.0;
+    for (name, quality) in accept_encoding.split(',').map(quality_item) {
+        if name.eq_ignore_ascii_case(encoding) {
+            return quality;
+        }
+        if name == "*" {
+            wildcard = quality;
         }
     }
+    wildcard
 }
 
-impl FromStr for Range {
-    type Err = Box<dyn StdError + Send + Sync + 'static>;
+/// Returns the language tag in a file name like `page.fr.html` or `page.en-US.html`: a
+/// two letter language, optionally followed by a two letter or three digit region.
+fn filename_language(path: &str) -> Option<&str> {
+    let name = path.rsplit('/').next()?;
+    let mut segments = name.rsplit('.');
+    segments.next()?;
+    let tag = segments.next()?;
+    // The language can't be the whole name, as in `fr.html`
+    segments.next()?;
+
+    let (language, region) = match tag.split_once('-') {
+        Some((language, region)) => (language, Some(region)),
+        None => (tag, None),
+    };
+    let language_valid = language.len() == 2 && language.bytes().all(|b| b.is_ascii_alphabetic());
+    let region_valid = match region {
+        Some(region) => {
+            (region.len() == 2 && region.bytes().all(|b| b.is_ascii_alphabetic()))
+                || (region.len() == 3 && region.bytes().all(|b| b.is_ascii_digit()))
+        }
+        None => true,
+    };
+    if language_valid && region_valid {
+        Some(tag)
+    } else {
+        None
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
-        match RANGE_HEADER_REGEX.captures(s) {
-            Some(matches) => {
-                let typ = &matches[1];
-                let start: u64 = matches[2].parse()?;
-                let end: u64 = matches[3].parse()?;
+/// Returns the languages of an `Accept-Language` header, most preferred first. Languages
+/// with a region are followed by the language alone as a fallback, so `fr-CH` looks for
+/// `fr` too.
+fn accepted_languages(accept_language: &str) -> Vec<String> {
+    let mut accepted: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .map(quality_item)
+        .filter(|(name, quality)| *quality > 0.0 && !name.is_empty() && *name != "*")
+        .collect();
+    accepted.sort_by(|a, b| b.1.total_cmp(&a.1));
 
-                Ok(Range {
-                    unit: typ.to_string(),
-                    start,
-                    end: Some(end),
-                })
+    let mut languages: Vec<String> = Vec::new();
+    for (name, _) in accepted {
+        let fallback = name.split_once('-').map(|(language, _)| language);
+        for language in std::iter::once(name).chain(fallback) {
+            if !languages.iter().any(|known| known.eq_ignore_ascii_case(language)) {
+                languages.push(language.to_string());
             }
-            None => match RANGE_HEADER_NO_END_REGEX.captures(s) {
-                Some(matches) => {
-                    let typ = &matches[1];
-                    let start: u64 = matches[2].parse()?;
+        }
+    }
+    languages
+}
 
-                    Ok(Range {
-                        unit: typ.to_string(),
-                        start,
-                        end: None,
-                    })
-                }
-                None => Err(Box::new(Error::new("invalid range header"))),
-            },
+/// Adds `field` to the `Vary` header of `response`, keeping the ones already listed.
+fn add_vary(response: &mut Response<'_>, field: &str) {
+    let vary = match response.headers().get_one("Vary") {
+        Some(vary) if vary.split(',').any(|f| f.trim().eq_ignore_ascii_case(field)) => return,
+        Some(vary) => format!("{}, {}", vary, field),
+        None => field.to_string(),
+    };
+    response.set_header(Header::new("Vary", vary));
+}
+
+/// Streams the body of `response` through the reader `wrap` makes of it, keeping the size it
+/// had as the `Content-Length`. Returns false without calling `wrap` if there is no body.
+fn wrap_body<'r, R>(response: &mut Response<'r>, wrap: impl FnOnce(Body<'r>) -> R) -> bool
+where
+    R: AsyncRead + Send + 'r,
+{
+    if response.body().is_none() {
+        return false;
+    }
+    if let Some(size) = response.body().preset_size() {
+        response.set_header(Header::new("Content-Length", format!("{}", size)));
+    }
+    let body = response.body_mut().take();
+    response.set_streamed_body(wrap(body));
+    true
+}
+
+/// Returns a `Content-Disposition` downloading the file as `name`. Names which aren't plain
+/// ASCII are sent percent-encoded as RFC 6266 asks for.
+fn attachment(name: &str) -> String {
+    if name.bytes().all(|b| b.is_ascii_graphic() || b == b' ') {
+        let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+        return format!("attachment; filename=\"{}\"", name);
+    }
+
+    let mut encoded = String::with_capacity(name.len() * 3);
+    for b in name.bytes() {
+        if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    format!("attachment; filename*=UTF-8''{}", encoded)
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986 in a path segment,
+/// so a name like `v1 & v1.1.tar.gz` can be put into a URL.
+fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for b in segment.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
         }
     }
+    encoded
+}
+
+/// Whether `path` is a source map, in any case and also stored compressed like `app.js.map.gz`.
+fn is_source_map(path: &str) -> bool {
+    let path = path.to_ascii_lowercase();
+    let path = path
+        .strip_suffix(".gz")
+        .or_else(|| path.strip_suffix(".br"))
+        .unwrap_or(&path);
+    path.ends_with(".map")
+}
+
+/// Returns the origin of a `Referer` like `https://example.com:8000/page`, which is the part
+/// up to the path, `https://example.com:8000`.
+fn referer_origin(referer: &str) -> Option<&str> {
+    let host_start = referer.find("://")? + 3;
+    let end = referer[host_start..]
+        .find(['/', '?', '#'])
+        .map_or(referer.len(), |end| host_start + end);
+    Some(&referer[..end]).filter(|_| end > host_start)
+}
+
+/// Whether a part of `path` is hidden by starting with a dot, like in `.well-known/x.txt`.
+pub(crate) fn is_hidden(path: &str) -> bool {
+    path.split('/')
+        .any(|part| part.starts_with('.') && part != "." && part != "..")
+}
+
+/// Inserts `banner` before the last `</body>` of `html`, in any case, or appends it to pages
+/// without one.
+fn inject_banner(mut html: Vec<u8>, banner: &str) -> Vec<u8> {
+    let end = html
+        .windows(7)
+        .rposition(|tag| tag.eq_ignore_ascii_case(b"</body>"))
+        .unwrap_or(html.len());
+    html.splice(end..end, banner.bytes());
+    html
+}
+
+/// Checks an `If-None-Match` or `If-Match` header against `etag`, using the weak comparison
+/// RFC 7232 asks for, so `W/"a"` matches `"a"`, or with `strong` the strong one, where only
+/// `"a"` matches `"a"`.
+fn etag_matches(if_none_match: &str, etag: &str, strong: bool) -> bool {
+    let mut tags = if_none_match.split(',').map(str::trim);
+    if strong {
+        return !etag.starts_with("W/") && tags.any(|tag| tag == "*" || tag == etag);
+    }
+    let etag = etag.trim_start_matches("W/");
+    tags.any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Marks the ETag of a response as weak, for bodies which differ from the file's bytes.
+fn weaken_etag(response: &mut Response<'_>, etag: &Option<String>) {
+    if let Some(etag) = etag.as_ref().filter(|etag| !etag.starts_with("W/")) {
+        response.set_header(Header::new("ETag", format!("W/{}", etag)));
+    }
+}
+
+/// Answers with a 500, describing what went wrong in the body of debug builds only.
+fn internal_error(response: &mut Response<'_>, description: &dyn fmt::Display) {
+    response.set_status(Status::InternalServerError);
+    #[cfg(debug_assertions)]
+    {
+        let body = description.to_string();
+        response.set_header(Header::new("Content-Type", "text/plain; charset=utf-8"));
+        response.set_sized_body(body.len(), Cursor::new(body));
+    }
+    #[cfg(not(debug_assertions))]
+    let _ = description;
+}
+
+/// Answers a failed filesystem operation with a 403 if access was denied, and with an
+/// `internal_error` for anything else.
+fn filesystem_error(response: &mut Response<'_>, err: &(dyn StdError + 'static)) {
+    let denied = err
+        .downcast_ref::<io::Error>()
+        .is_some_and(|err| err.kind() == io::ErrorKind::PermissionDenied);
+    if denied {
+        response.set_status(Status::Forbidden);
+    } else {
+        internal_error(response, &err);
+    }
 }
 
 /// StaticFileServer is your fairing for the static file server.
@@ -162,8 +408,12 @@ pub struct StaticFileServer<T>
 where
     T: FileSystem + Sized + Send + Sync,
 {
-    fs: T,
+    /// Shared, so archive downloads can open their files while they're being streamed.
+    fs: Arc<T>,
     options: Options,
+    open_permits: Option<Arc<Semaphore>>,
+    /// Registered once, and shared with the listings being streamed.
+    templates: Arc<Handlebars<'static>>,
 }
 
 impl<T> StaticFileServer<T>
@@ -176,348 +426,5012 @@ where
     /// `prefix` is the prefix the serve from.
     ///
     /// You can set a prefix of /assets and only requests to /assets/* will be served.
+    ///
+    /// Fails if the filesystem has nothing to serve, like a `LocalFileSystem` over a missing
+    /// directory, see `FileSystem::verify`.
     pub fn new(fs: T, options: Options) -> Result<Self, Box<dyn StdError>> {
-        Ok(StaticFileServer { fs, options })
+        fs.verify()?;
+        let open_permits = options
+            .max_concurrent_opens()
+            .map(|max| Arc::new(Semaphore::new(max)));
+        Ok(StaticFileServer {
+            fs: Arc::new(fs),
+            options,
+            open_permits,
+            templates: Arc::new(listing_templates()),
+        })
     }
 
-    async fn handle_directory_listing<'r>(&self, req_path: &str, response: &mut Response<'r>) {
-        if !req_path.ends_with('/') && req_path != "" {
-            let mut redirect_path = String::new();
-            redirect_path.push('/');
-            redirect_path.push_str(req_path);
-            redirect_path.push('/');
-            response.set_status(Status::Found);
-            response.set_header(Header::new("Location", redirect_path));
-            return;
+    /// Returns what the server has been configured with, e.g. to print it at startup when
+    /// tracking down a misconfiguration.
+    pub fn effective_options(&self) -> EffectiveOptions {
+        let options = &self.options;
+        let map = |map: &std::collections::HashMap<String, String>| {
+            map.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+        };
+        EffectiveOptions {
+            prefix: options.prefix().to_string(),
+            canonical_host: options.canonical_host().cloned(),
+            allow_directory_listing: options.allow_directory_listing(),
+            listing_dirs: options.listing_dirs().to_vec(),
+            listing_disabled_status: options.listing_disabled_status().map(|status| status.code),
+            listing_title: options.listing_title().cloned(),
+            disable_directory_redirect: options.disable_directory_redirect(),
+            empty_directory_not_found: options.empty_directory_not_found(),
+            directory_listing_default_index: options.directory_listing_default_index().cloned(),
+            hide_index_in_url: options.hide_index_in_url(),
+            hidden_index_files: options.hidden_index_files().to_vec(),
+            plus_as_space: options.plus_as_space(),
+            try_extensions: options.try_extensions().to_vec(),
+            default_content_type: options.default_content_type().to_string(),
+            not_found_response: options.not_found_response().map(|response| response.status().code),
+            server_header: options.server_header().map(|server| server.cloned()),
+            cross_origin_isolation: options.cross_origin_isolation(),
+            mime_overrides: MODERN_MIME_TYPES
+                .iter()
+                .map(|(extension, mime)| (extension.to_string(), mime.to_string()))
+                .collect(),
+            charset_map: map(options.charset_map()),
+            immutable_prefix: options.immutable_prefix().cloned(),
+            no_store_pattern: options.no_store_pattern().map(|p| p.as_str().to_string()),
+            no_range_pattern: options.no_range_pattern().map(|p| p.as_str().to_string()),
+            source_map_origins: options.source_map_origins().to_vec(),
+            disable_last_modified: options.disable_last_modified(),
+            supports_ranges: self.fs.supports_ranges(),
+            gzip_only_assets: options.gzip_only_assets(),
+            precompressed_variants: options.precompressed_variants(),
+            content_language_from_filename: options.content_language_from_filename(),
+            language_negotiation: options.language_negotiation(),
+            stream_directory_listing: options.stream_directory_listing(),
+            compress_min_size: options.compress_min_size(),
+            small_file_threshold: options.small_file_threshold(),
+            archive_download: options.archive_download(),
+            max_path_depth: options.max_path_depth(),
+            max_path_length: options.max_path_length(),
+            max_listing_entries: options.max_listing_entries(),
+            max_concurrent_opens: options.max_concurrent_opens(),
+            maintenance: options.maintenance().is_some(),
+            slow_file_warning: options.slow_file_warning().map(SlowFileWarning::threshold),
+            transform: options.transform().is_some(),
+            html_banner: options.html_banner().map(str::to_string),
+            throttle_bytes_per_sec: options.throttle_bytes_per_sec(),
+            request_filter: options.request_filter().is_some(),
+            bytes_sent_callback: options.bytes_sent_callback().is_some(),
+            query_in_etag: options.query_in_etag(),
+            etag_strength: options.etag_strength().map(|strength| strength.as_str().to_string()),
+            serve_hidden_paths: options.serve_hidden_paths(),
+            list_hidden_paths: options.list_hidden_paths(),
+            allow_checksum_query: options.allow_checksum_query(),
+            normalize_backslashes: options.normalize_backslashes(),
+            listing_feed: options.listing_feed(),
+            disposition_by_mime: options
+                .disposition_by_mime()
+                .iter()
+                .map(|(mime, disposition)| (mime.clone(), disposition.as_str().to_string()))
+                .collect(),
         }
+    }
 
-        match self.fs.entries(&req_path).await {
-            Ok(entries) => {
-                let mut hbs = Handlebars::new();
-                hbs.register_template_string(
-                    "directory_listing",
-                    include_str!("../templates/directory_listing.hbs"),
-                ).unwrap();
-                let entries: Vec<TemplateEntry> =
-                    entries.iter().map(|e| TemplateEntry::from(e)).collect();
-                let context = DirectoryListingContext {
-                    directory: req_path.to_string(),
-                    entries,
-                };
-                match hbs.render("directory_listing", &context) {
-                    Ok(s) => {
-                        response.set_status(Status::Ok);
-                        response.set_sized_body(s.len(), Cursor::new(s));
-                    }
-                    Err(e) => {
-                        response.set_status(Status::InternalServerError);
-                        #[cfg(debug_assertions)]
-                        response.set_sized_body(e.desc.len(), Cursor::new(e.desc));
-                    }
-                }
+    /// Takes one of the permits for open files if their number is capped, failing if all of
+    /// them are taken.
+    fn open_permit(&self) -> Result<Option<OwnedSemaphorePermit>, TryAcquireError> {
+        match self.open_permits {
+            Some(ref permits) => permits.clone().try_acquire_owned().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Redirects a directory request without a trailing slash to its canonical form, so
+    /// relative links in the served page resolve against the directory.
+    ///
+    /// The target can't lead back to the request: the prefix ends in a slash and `req_path`
+    /// doesn't, while decoding never drops a slash the request path ended in.
+    fn redirect_to_directory(&self, req_path: &str, response: &mut Response<'_>) {
+        response.set_status(Status::Found);
+        response.set_header(Header::new("Location", self.directory_url(req_path)));
+    }
+
+    /// The URL path of the `req_path` directory, with a trailing slash and each segment
+    /// percent-encoded, so entry names can be appended to it.
+    fn directory_url(&self, req_path: &str) -> String {
+        let mut url = self.options.prefix().to_string();
+        for segment in req_path.split('/').filter(|s| !s.is_empty()) {
+            url.push_str(&encode_segment(segment));
+            url.push('/');
+        }
+        url
+    }
+
+    /// Whether bodies of `length` bytes are compressed for clients accepting an encoding. Tiny
+    /// bodies would only grow from the encoding overhead.
+    fn compressible(&self, length: u64) -> bool {
+        cfg!(feature = "content_encoding") && length >= self.options.compress_min_size()
+    }
+
+    /// The encoding a body of `length` bytes is compressed with on the fly for `request`, if
+    /// any. GET and HEAD requests both ask this, so their headers agree.
+    fn live_encoding(&self, request: &Request<'_>, length: u64) -> Option<&'static str> {
+        if !self.compressible(length) {
+            return None;
+        }
+        ["gzip", "deflate"]
+            .into_iter()
+            .find(|encoding| accepts_encoding(request, encoding))
+    }
+
+    /// Finds the precompressed sibling of `path` to serve to `request`, along with its
+    /// encoding, size and modification date. Of the encodings the client accepts, the one it
+    /// gives the highest quality wins.
+    async fn precompressed_variant(
+        &self,
+        request: &Request<'_>,
+        path: &str,
+    ) -> Option<(&'static str, String, u64, SystemTime)> {
+        let accept_encoding = request.headers().get_one("Accept-Encoding")?;
+        let mut best = None;
+        let mut best_quality = 0.0;
+        for (encoding, extension) in [("br", "br"), ("gzip", "gz")] {
+            let quality = encoding_quality(accept_encoding, encoding);
+            if quality <= best_quality {
+                continue;
             }
-            Err(_) => {
-                response.set_status(Status::Forbidden);
+            let variant = format!("{}.{}", path, extension);
+            if let Ok(Some(FileMeta::File(size, modified))) = self.fs.stat(&variant).await {
+                best = Some((encoding, variant, size, modified));
+                best_quality = quality;
             }
         }
+        best
     }
-}
 
-#[rocket::async_trait]
-impl<T: 'static> Fairing for StaticFileServer<T>
-where
-    T: FileSystem + Sized + Send + Sync,
-{
-    fn info(&self) -> Info {
-        Info {
-            name: "static_file_server",
-            kind: Kind::Response,
+    /// Finds the language variant of `path` to serve to `request`, like `page.fr.html` for
+    /// `page.html`, along with its metadata. The most preferred language with a variant
+    /// wins.
+    async fn language_variant(
+        &self,
+        request: &Request<'_>,
+        path: &str,
+    ) -> Option<(String, FileMeta)> {
+        let accept_language = request.headers().get_one("Accept-Language")?;
+        // Files which already are a variant are served as they are
+        if filename_language(path).is_some() {
+            return None;
+        }
+        let name_start = path.rfind('/').map_or(0, |i| i + 1);
+        let dot = name_start + path[name_start..].rfind('.').filter(|dot| *dot > 0)?;
+        let (stem, extension) = path.split_at(dot);
+
+        for language in accepted_languages(accept_language) {
+            let variant = format!("{}.{}{}", stem, language, extension);
+            // Only proper language codes make it into a path
+            if filename_language(&variant) != Some(language.as_str()) {
+                continue;
+            }
+            if let Ok(Some(FileMeta::File(size, modified))) = self.fs.stat(&variant).await {
+                return Some((variant, FileMeta::File(size, modified)));
+            }
         }
+        None
     }
 
-    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
-        // Only handle requests which aren't otherwise handled.
-        if response.status() != Status::NotFound {
-            return;
+    /// Whether hidden entries are listed, which they aren't if they can't be served anyway.
+    fn lists_hidden(&self) -> bool {
+        self.options.list_hidden_paths() && self.options.serve_hidden_paths()
+    }
+
+    /// Answers with the configured not found response, or a plain 404 for Rocket to fill in.
+    fn not_found(&self, response: &mut Response<'_>) {
+        match self.options.not_found_response() {
+            Some(not_found) => {
+                let body = not_found.body().to_string();
+                response.set_status(not_found.status());
+                response.set_header(Header::new("Content-Type", not_found.content_type().to_string()));
+                response.set_sized_body(body.len(), Cursor::new(body));
+            }
+            None => response.set_status(Status::NotFound),
         }
+    }
 
-        // Only handle requests which include our prefix
-        let uri = request.uri().to_string();
-        if !((request.method() == Method::Get || request.method() == Method::Head)
-            && uri.starts_with(&self.options.prefix()))
-        {
-            return;
+    /// Returns the path of the default index file inside the `req_path` directory, if one is
+    /// configured and exists.
+    async fn directory_index(&self, req_path: &str) -> Option<String> {
+        let index = self.options.directory_listing_default_index()?;
+        let mut index_path = req_path.trim_end_matches('/').to_string();
+        if !index_path.is_empty() {
+            index_path.push('/');
         }
+        index_path.push_str(index);
 
-        // Strip out the prefix to get the normal file path
-        let req_path = uri.replacen(&self.options.prefix(), "", 1);
+        if self.fs.is_file(&index_path).await {
+            Some(index_path)
+        } else {
+            None
+        }
+    }
 
-        // Fail on paths outside of the given path
-        if !self.fs.path_valid(&req_path).await {
-            response.set_status(Status::Forbidden);
-            return;
+    /// Whether source maps may be sent in response to `request`: always without
+    /// `source_map_origins`, otherwise if it comes from one of them.
+    fn source_maps_allowed(&self, request: &Request<'_>) -> bool {
+        let map_origins = self.options.source_map_origins();
+        if map_origins.is_empty() {
+            return true;
         }
+        let headers = request.headers();
+        let origin = headers
+            .get_one("Origin")
+            .or_else(|| headers.get_one("Referer").and_then(referer_origin));
+        origin.is_some_and(|origin| map_origins.iter().any(|allowed| allowed == origin))
+    }
 
-        // If it is no file, we check if it's a directory, if it is, we list the
-        // directory contents if enabled in the options. Otherwise we return a not found.
-        if !self.fs.is_file(&req_path).await {
-            if self.fs.is_dir(&req_path).await && self.options.allow_directory_listing() {
-                self.handle_directory_listing(&req_path, response).await;
+    /// Collects every file below the `req_path` directory, to be opened once the archive
+    /// gets to it. Source maps are left out unless `with_source_maps`.
+    async fn archive_entries(
+        &self,
+        req_path: &str,
+        with_source_maps: bool,
+    ) -> Result<Vec<ArchiveEntry>, Box<dyn StdError>>
+    where
+        T: 'static,
+    {
+        fn join(dir: &str, name: &str) -> String {
+            if dir.is_empty() {
+                name.to_string()
             } else {
-                response.set_status(Status::NotFound);
+                format!("{}/{}", dir, name)
             }
-            return;
         }
 
-        // Let's set the mime type here, this can't possibly go wrong anymore *cough*.
-        {
-            let mime = mime_guess::from_path(Path::new(&req_path)).first_or_octet_stream();
-            response.set_header(Header::new("Content-Type", mime.to_string()));
+        let root = req_path.trim_end_matches('/');
+        let mut dirs = vec![String::new()];
+        let mut archive_entries = Vec::new();
+        while let Some(dir) = dirs.pop() {
+            let entries = self.fs.entries(join(root, &dir)).await?;
+            for entry in entries {
+                // Archives are listings with the files attached, so hidden paths stay out of
+                // them the same way
+                if !self.lists_hidden() && is_hidden(entry.name()) {
+                    continue;
+                }
+                let path = join(&dir, entry.name());
+                match entry {
+                    Entry::Dir(_) => dirs.push(path),
+                    Entry::File(..) if !with_source_maps && is_source_map(&path) => {}
+                    Entry::File(_, size, modified) => {
+                        let fs = Arc::clone(&self.fs);
+                        let permits = self.open_permits.clone();
+                        let full_path = join(root, &path);
+                        let open: OpenEntry = Box::pin(async move {
+                            // Archives wait for a permit rather than failing halfway through
+                            let permit = match permits {
+                                Some(permits) => {
+                                    Some(permits.acquire_owned().await.map_err(io::Error::other)?)
+                                }
+                                None => None,
+                            };
+                            let reader = fs
+                                .open(full_path, None)
+                                .await
+                                .map_err(|err| io::Error::other(err.to_string()))?;
+                            let reader: Box<dyn AsyncRead + Send + Unpin> = match permit {
+                                Some(permit) => Box::new(PermitReader::new(reader, permit)),
+                                None => Box::new(reader),
+                            };
+                            Ok(reader)
+                        });
+                        archive_entries.push(ArchiveEntry {
+                            path,
+                            size,
+                            modified,
+                            open,
+                        });
+                    }
+                }
+            }
+        }
+
+        archive_entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(archive_entries)
+    }
+
+    async fn handle_archive_download<'r>(
+        &self,
+        req_path: &str,
+        format: &str,
+        request: &Request<'_>,
+        response: &mut Response<'r>,
+    ) where
+        T: 'static,
+    {
+        let archive_format = match format {
+            "zip" => ArchiveFormat::Zip,
+            "tar" => ArchiveFormat::Tar,
+            #[cfg(feature = "content_encoding")]
+            "tar.gz" => ArchiveFormat::Tar,
+            _ => {
+                response.set_status(Status::BadRequest);
+                return;
+            }
         };
 
-        // Get the file modification date and the If-Modified-Since header value
-        let modified = self.fs.last_modified(&req_path).await.expect("no modified since");
-        let modified: DateTime<Utc> = DateTime::from(modified);
-        let if_modified_since = request.headers().get("If-Modified-Since").next();
+        let with_source_maps = self.source_maps_allowed(request);
+        let entries = match self.archive_entries(req_path, with_source_maps).await {
+            Ok(entries) => entries,
+            Err(err) => {
+                filesystem_error(response, &*err);
+                return;
+            }
+        };
 
-        // Only on a GET request: If the If-Modified-Since header and the modified time of the file are the same, we
-        // respond with a 304 here
-        if request.method() == Method::Get {
-            if let Some(time) = if_modified_since {
-                if let Ok(time) = Utc.datetime_from_str(&time, LAST_MODIFIED_DATE_FORMAT) {
-                    let duration: chrono::Duration = time.signed_duration_since(modified);
-                    if duration.num_seconds() == 0 {
-                        response.set_status(Status::NotModified);
-                        return;
-                    };
+        let name = req_path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+            .unwrap_or("archive");
+        let archive = match ArchiveStream::new(archive_format, entries) {
+            Ok(archive) => archive,
+            Err(err) => {
+                internal_error(response, &err);
+                return;
+            }
+        };
+        response.set_status(Status::Ok);
+
+        #[cfg(feature = "content_encoding")]
+        {
+            if format == "tar.gz" {
+                response.set_header(Header::new("Content-Type", "application/gzip"));
+                let disposition = attachment(&format!("{}.tar.gz", name));
+                response.set_header(Header::new("Content-Disposition", disposition));
+                response.set_streamed_body(GzipEncoder::new(BufReader::new(archive)));
+                return;
+            }
+        }
+
+        response.set_header(Header::new("Content-Type", archive_format.content_type()));
+        let disposition = attachment(&format!("{}.{}", name, archive_format.extension()));
+        response.set_header(Header::new("Content-Disposition", disposition));
+        response.set_streamed_body(archive);
+    }
+
+    /// Responds with the files of the directory at `req_path` as an RSS feed.
+    fn handle_listing_feed(
+        &self,
+        req_path: &str,
+        entries: Vec<&Entry>,
+        request: &Request<'_>,
+        response: &mut Response<'_>,
+    ) {
+        let mut files: Vec<(&Entry, SystemTime)> = entries
+            .into_iter()
+            .filter_map(|e| match e {
+                Entry::File(_, _, modified) => Some((e, *modified)),
+                Entry::Dir(_) => None,
+            })
+            .collect();
+        files.sort_by(|(_, a), (_, b)| b.cmp(a));
+        if let Some(max) = self.options.max_listing_entries() {
+            files.truncate(max);
+        }
+
+        // Feed readers need absolute links, which we can only make if we know the host. The
+        // one we redirect everyone to is the right one, whatever the client asked for.
+        let path = self.directory_url(req_path);
+        let host = match self.options.canonical_host() {
+            Some(host) => Some(host.clone()),
+            None => request.host().map(ToString::to_string),
+        };
+        let url = match host {
+            Some(host) => {
+                let scheme = if request.rocket().config().tls_enabled() {
+                    "https"
+                } else {
+                    "http"
                 };
-            };
+                format!("{}://{}{}", scheme, host, path)
+            }
+            None => path,
+        };
+        let context = FeedContext {
+            directory: req_path.to_string(),
+            title: self.options.listing_title().cloned(),
+            entries: files
+                .into_iter()
+                .map(|(e, _)| {
+                    let entry = TemplateEntry::from(e);
+                    let url = format!("{}{}", url, entry.href());
+                    FeedEntry { entry, url }
+                })
+                .collect(),
+            url,
+        };
+
+        match self.templates.render("directory_listing_rss", &context) {
+            Ok(feed) => {
+                response.set_status(Status::Ok);
+                response.set_header(Header::new(
+                    "Content-Type",
+                    "application/rss+xml; charset=utf-8",
+                ));
+                response.set_header(Header::new("Accept-Ranges", "none"));
+                response.set_sized_body(feed.len(), Cursor::new(feed));
+            }
+            Err(err) => internal_error(response, &err),
         }
+    }
 
-        let size = match self.fs.size(&req_path).await {
-            Ok(s) => s,
-            Err(_) => {
-                response.set_status(Status::Forbidden);
+    /// Reads the file at `req_path` through a checksum of `algorithm` and responds with it.
+    async fn handle_checksum(&self, req_path: &str, algorithm: &str, response: &mut Response<'_>) {
+        let mut checksum = match Checksum::new(algorithm) {
+            Some(checksum) => checksum,
+            None => {
+                response.set_status(Status::BadRequest);
                 return;
             }
         };
 
-        // In case someone heads the file, we inform him about the content length and
-        // that we support byte ranges.
-        if request.method() == Method::Head {
-            response.set_header(Header::new("Accept-Ranges", "bytes"));
-            response.set_header(Header::new("Content-Length", format!("{}", size)));
-            response.set_status(Status::Ok);
-            return;
+        let _permit = match self.open_permit() {
+            Ok(permit) => permit,
+            Err(_) => {
+                response.set_status(Status::ServiceUnavailable);
+                return;
+            }
+        };
+        let mut f = match self.fs.open(req_path, None).await {
+            Ok(f) => f,
+            Err(err) => {
+                filesystem_error(response, &*err);
+                return;
+            }
+        };
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            match f.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(len) => checksum.update(&buf[..len]),
+                Err(err) => {
+                    internal_error(response, &err);
+                    return;
+                }
+            }
         }
 
-        // Let's parse the range header if it exists
-        let range_header = request.headers().get_one("Range").unwrap_or("");
+        let hex = checksum.hex();
+        response.set_status(Status::Ok);
+        response.set_header(Header::new("Content-Type", "text/plain; charset=utf-8"));
+        response.set_sized_body(hex.len(), Cursor::new(hex));
+    }
 
-        // If we get a multipart range request, we more or less fail gracefully here for the moment.
-        // We simply set the range here to an error and send the complete file cause of that.
-        // TODO: Support multipart ranges
-        let range: Result<Range, Box<dyn StdError + Send + Sync + 'static>> = if range_header.contains(',') {
-            Err(Box::new(Error::new("multipart ranges not supported")))
+    /// Splits `req_path` into links to the served root and every directory down to it.
+    fn breadcrumbs(&self, req_path: &str) -> Vec<Breadcrumb> {
+        let mut href = self.options.prefix().to_string();
+        let mut breadcrumbs = vec![Breadcrumb {
+            name: self
+                .options
+                .listing_title()
+                .cloned()
+                .unwrap_or_else(|| "/".to_string()),
+            href: href.clone(),
+        }];
+        for segment in req_path.split('/').filter(|s| !s.is_empty()) {
+            href.push_str(&encode_segment(segment));
+            href.push('/');
+            breadcrumbs.push(Breadcrumb {
+                name: segment.to_string(),
+                href: href.clone(),
+            });
+        }
+        breadcrumbs
+    }
+
+    async fn handle_directory_listing<'r>(
+        &self,
+        req_path: &str,
+        request: &'r Request<'_>,
+        response: &mut Response<'r>,
+    ) {
+        let base = if self.options.disable_directory_redirect() {
+            self.directory_url(req_path)
         } else {
-            range_header.parse::<Range>()
+            if !req_path.ends_with('/') && !req_path.is_empty() {
+                self.redirect_to_directory(req_path, response);
+                return;
+            }
+            String::new()
         };
 
-        // Set the start byte for the request
-        let start = match range {
-            Ok(ref range) => range.start,
-            Err(_) => 0,
-        };
+        match self.fs.entries(req_path).await {
+            Ok(entries) => {
+                // A directory of nothing but entries which are never listed is empty as well
+                let entries: Vec<&Entry> = entries
+                    .iter()
+                    .filter(|e| match e {
+                        Entry::File(name, _, _) => {
+                            !self.options.hidden_index_files().contains(name)
+                        }
+                        Entry::Dir(_) => true,
+                    })
+                    .filter(|e| self.lists_hidden() || !is_hidden(e.name()))
+                    .collect();
+                let empty = entries.is_empty();
+                if empty && self.options.empty_directory_not_found() {
+                    self.not_found(response);
+                    return;
+                }
 
-        // Otherwise we try to send the file, which should work since that size above should have
-        // worked as well.
-        match self.fs.open(&req_path, Some(0)).await {
-            Ok(f) => {
-                response.set_status(Status::Ok);
-                response.set_header(Header::new("Accept-Ranges", "bytes"));
-                response.set_header(Header::new(
-                    "Last-Modified",
-                    modified.format(LAST_MODIFIED_DATE_FORMAT).to_string(),
-                ));
+                // An empty filter is the same as no filter at all
+                let filter = request
+                    .query_value::<&str>("filter")
+                    .and_then(Result::ok)
+                    .filter(|f| !f.is_empty());
+                let entries: Vec<&Entry> = entries
+                    .into_iter()
+                    .filter(|e| match filter {
+                        Some(filter) => e.name().contains(filter),
+                        None => true,
+                    })
+                    .collect();
 
-                // We shadow and box our f here to support different Read implementations
-                let mut f: Box<<T as FileSystem>::Read> = Box::new(f);
+                let format = request.query_value::<&str>("format").and_then(Result::ok);
+                if self.options.listing_feed() && format == Some("rss") {
+                    self.handle_listing_feed(req_path, entries, request, response);
+                    return;
+                }
 
-                // If we got a range header, we set the corresponding headers here and
-                // set f to a limit reader so it will stop when it reached the range len.
-                if let Ok(ref range) = range {
-                    let mut content_length = size - start + 1;
-                    if let Some(len) = range.len() {
-                        f = f.take(len).into_inner();
-                        content_length = len;
-                    }
-                    response
-                        .set_header(Header::new("Content-Length", format!("{}", content_length)));
-                    let range_end = start + content_length;
-                    response.set_header(Header::new(
-                        "Content-Range",
-                        format!("{}={}-{}/{}", range.unit, range.start, range_end, size),
-                    ));
-                    response.set_status(Status::PartialContent);
+                let mut entries: Vec<TemplateEntry> =
+                    entries.into_iter().map(TemplateEntry::from).collect();
+                let mut truncated = false;
+                if let Some(max) = self.options.max_listing_entries() {
+                    truncated = entries.len() > max;
+                    entries.truncate(max);
                 }
+                let context = DirectoryListingContext {
+                    directory: req_path.to_string(),
+                    base,
+                    title: self.options.listing_title().cloned(),
+                    breadcrumbs: self.breadcrumbs(req_path),
+                    filter: filter.map(str::to_string),
+                    entries,
+                    truncated,
+                    empty,
+                };
+                let listing = if self.options.stream_directory_listing() {
+                    ListingStream::new(self.templates.clone(), context)
+                        .map(|stream| Listing::Streamed(Box::new(stream)))
+                } else {
+                    self.templates.render("directory_listing", &context).map(Listing::Rendered)
+                };
+                match listing {
+                    Ok(listing) => {
+                        response.set_status(Status::Ok);
+                        response.set_header(Header::new("Content-Type", "text/html; charset=utf-8"));
+                        // Listings are rendered on every request, so Range headers are ignored
+                        response.set_header(Header::new("Accept-Ranges", "none"));
 
-                #[cfg(target = "content_encoding")]
-                {
-                    // In case the client accepts encodings, we handle these
-                    if let Some(encodings) = request.headers().get_one("Accept-Encoding") {
-                        if encodings.contains("gzip") {
-                            let mut encoder = GzEncoder::new(f, Compression::default());
-                            response.set_header(Header::new("Content-Encoding", "gzip"));
-                            response.set_streamed_body(encoder);
-                            return;
-                        } else if encodings.contains("deflate") {
-                            let mut encoder = DeflateEncoder::new(f, Compression::default());
-                            response.set_header(Header::new("Content-Encoding", "deflate"));
-                            response.set_streamed_body(encoder);
-                            return;
+                        #[cfg(feature = "content_encoding")]
+                        {
+                            // Listings of large directories are worth compressing as well
+                            response.set_header(Header::new("Vary", "Accept-Encoding"));
+                            let encoding = ["gzip", "deflate"]
+                                .into_iter()
+                                .find(|encoding| accepts_encoding(request, encoding));
+                            if let Some(encoding) = encoding {
+                                let body: Box<dyn AsyncBufRead + Send + Unpin> = match listing {
+                                    Listing::Rendered(s) => Box::new(Cursor::new(s.into_bytes())),
+                                    Listing::Streamed(stream) => Box::new(BufReader::new(stream)),
+                                };
+                                response.set_header(Header::new("Content-Encoding", encoding));
+                                if encoding == "gzip" {
+                                    response.set_streamed_body(GzipEncoder::new(body));
+                                } else {
+                                    response.set_streamed_body(DeflateEncoder::new(body));
+                                }
+                                return;
+                            }
+                        }
+
+                        match listing {
+                            Listing::Rendered(s) => {
+                                response.set_sized_body(s.len(), Cursor::new(s));
+                            }
+                            Listing::Streamed(stream) => response.set_streamed_body(stream),
                         }
-                    };
+                    }
+                    Err(err) => internal_error(response, &err),
                 }
+            }
+            Err(err) => filesystem_error(response, &*err),
+        }
+    }
 
-                response.set_streamed_body(f);
+    /// Serves a request the fairing is responsible for, `uri` being its path.
+    async fn serve<'r>(
+        &self,
+        request: &'r Request<'_>,
+        response: &mut Response<'r>,
+        method: Method,
+        uri: &str,
+        is_root: bool,
+        started: Instant,
+    ) where
+        T: 'static,
+    {
+        if let Some(canonical_host) = self.options.canonical_host() {
+            let host = request.host().map(ToString::to_string);
+            if host.is_some_and(|host| !host.eq_ignore_ascii_case(canonical_host)) {
+                let scheme = if request.rocket().config().tls_enabled() {
+                    "https"
+                } else {
+                    "http"
+                };
+                let location = format!("{}://{}{}", scheme, canonical_host, request.uri());
+                response.set_status(Status::MovedPermanently);
+                response.set_header(Header::new("Location", location));
+                response.set_sized_body(0, Cursor::new(""));
+                return;
             }
-            Err(_) => {
-                // TODO: What else could go wrong here? IMO it can be just no permissions
+        }
+
+        if let Some(filter) = self.options.request_filter() {
+            if !filter(request) {
                 response.set_status(Status::Forbidden);
+                response.set_sized_body(0, Cursor::new(""));
+                return;
             }
         }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    #[allow(unused)]
-    use super::fs::*;
-    use super::*;
-    use rocket;
-    use rocket::http::{Header, Status};
-    use rocket::local::blocking::Client;
+        if self.options.cross_origin_isolation() {
+            response.set_header(Header::new("Cross-Origin-Opener-Policy", "same-origin"));
+            response.set_header(Header::new("Cross-Origin-Embedder-Policy", "require-corp"));
+        }
 
-    #[test]
-    fn test_with_local_filesystem() {
-        let fs = LocalFileSystem::new("src");
-        let options = OptionsBuilder::new().prefix("/test").into();
-        let rocket = rocket::ignite().attach(StaticFileServer::new(fs, options).unwrap());
-        let client = Client::new(rocket).expect("valid rocket");
+        match self.options.server_header() {
+            Some(Some(server)) => {
+                response.set_header(Header::new("Server", server.clone()));
+            }
+            Some(None) => response.remove_header("Server"),
+            None => {}
+        }
 
-        // Test simply getting a file
-        let resp = client.get("/test/lib.rs").dispatch();
-        assert_eq!(resp.status(), Status::Ok);
-        assert_eq!(
-            resp.headers()
-                .get_one("Content-Type")
-                .expect("no content type"),
-            "text/x-rust"
-        );
+        // While in maintenance, nothing gets served
+        if let Some(maintenance) = self.options.maintenance() {
+            response.set_status(Status::ServiceUnavailable);
+            response.set_header(Header::new(
+                "Retry-After",
+                format!("{}", maintenance.retry_after()),
+            ));
+            if let Some(page) = maintenance.page_content() {
+                response.set_header(Header::new("Content-Type", "text/html; charset=utf-8"));
+                response.set_sized_body(page.len(), Cursor::new(page.clone()));
+            } else {
+                response.set_sized_body(0, Cursor::new(""));
+            }
+            return;
+        }
+
+        // The prefix without its trailing slash is the served root directory
+        if is_root {
+            response.set_status(Status::Found);
+            response.set_header(Header::new("Location", self.options.prefix().to_string()));
+            return;
+        }
+
+        // Strip out the prefix to get the normal file path. The URI path already excludes any
+        // `?query`, so cache busting URLs like `/app.js?v=1` resolve to the file itself.
+        let raw_path = RawStr::new(&uri[self.options.prefix().len()..]);
+        if let Some(max_length) = self.options.max_path_length() {
+            if raw_path.len() > max_length {
+                response.set_status(Status::UriTooLong);
+                response.set_sized_body(0, Cursor::new(""));
+                return;
+            }
+        }
+        let decoded = if self.options.plus_as_space() {
+            raw_path.url_decode()
+        } else {
+            raw_path.percent_decode()
+        };
+        let mut req_path = match decoded {
+            Ok(path) => path.into_owned(),
+            Err(_) => {
+                response.set_status(Status::BadRequest);
+                return;
+            }
+        };
+
+        if self.options.normalize_backslashes() {
+            req_path = req_path.replace('\\', "/");
+        }
+
+        // Decoding may have turned `%2e%2e`, `..%2f` or `..%5C` into a way up out of the root.
+        // Backslashes count as separators here either way, since they are on Windows.
+        if req_path.split(['/', '\\']).any(|part| part == "..") {
+            response.set_status(Status::Forbidden);
+            return;
+        }
+
+        // Don't even bother looking up pathologically deep paths
+        if let Some(max_depth) = self.options.max_path_depth() {
+            if req_path.split('/').filter(|s| !s.is_empty()).count() > max_depth {
+                self.not_found(response);
+                return;
+            }
+        }
+
+        // Fail on paths outside of the given path
+        if !self.fs.path_valid(&req_path).await {
+            response.set_status(Status::Forbidden);
+            return;
+        }
+
+        if !self.options.serve_hidden_paths() && is_hidden(&req_path) {
+            self.not_found(response);
+            return;
+        }
+
+        // A single stat tells us whether we got a file or a directory, along with a file's
+        // size and modification date. The fallbacks below only look further if it's no file.
+        let mut meta = if req_path.is_empty() {
+            // The root is a directory, whatever the backend makes of an empty path
+            Some(FileMeta::Dir)
+        } else {
+            match self.fs.stat(&req_path).await {
+                Ok(meta) => meta,
+                Err(err) => {
+                    filesystem_error(response, &*err);
+                    return;
+                }
+            }
+        };
+        let is_file = matches!(meta, Some(FileMeta::File(..)));
+
+        // Index files are served at their directory's URL instead
+        if let (true, Some(index), true) = (
+            self.options.hide_index_in_url(),
+            self.options.directory_listing_default_index(),
+            is_file,
+        ) {
+            let dir = req_path.strip_suffix(index.as_str());
+            if dir.is_some_and(|dir| dir.is_empty() || dir.ends_with('/')) {
+                let mut location = format!("{}{}", self.options.prefix(), dir.unwrap_or(""));
+                if let Some(query) = request.uri().query() {
+                    location = format!("{}?{}", location, query);
+                }
+                response.set_status(Status::MovedPermanently);
+                response.set_header(Header::new("Location", location));
+                response.set_sized_body(0, Cursor::new(""));
+                return;
+            }
+        }
+
+        // A missing file may still be stored gzipped only, which we can hand out as is to
+        // clients accepting gzip or decompress for everyone else.
+        let accepts_gzip = accepts_encoding(request, "gzip");
+        let gzip_path = format!("{}.gz", req_path);
+        let mut gzip_only = false;
+        if self.options.gzip_only_assets()
+            && (accepts_gzip || cfg!(feature = "content_encoding"))
+            && !is_file
+        {
+            if let Ok(Some(gzip_meta @ FileMeta::File(..))) = self.fs.stat(&gzip_path).await {
+                gzip_only = true;
+                meta = Some(gzip_meta);
+            }
+        }
+
+        // Pretty URLs like `/about` may refer to `about.html`
+        if !gzip_only && !req_path.is_empty() && !req_path.ends_with('/') && !is_file {
+            for extension in self.options.try_extensions() {
+                let candidate = format!("{}.{}", req_path, extension.trim_start_matches('.'));
+                if let Ok(Some(candidate_meta @ FileMeta::File(..))) =
+                    self.fs.stat(&candidate).await
+                {
+                    req_path = candidate;
+                    meta = Some(candidate_meta);
+                    break;
+                }
+            }
+        }
+
+        // If it is a directory, we serve its index file or list the directory contents if
+        // enabled in the options. Otherwise we return a not found.
+        if let Some(FileMeta::Dir) = meta {
+            // Directories may be downloaded as a whole
+            if self.options.archive_download() {
+                if let Some(format) = request.query_value::<&str>("download").and_then(Result::ok) {
+                    self.handle_archive_download(&req_path, format, request, response).await;
+                    return;
+                }
+            }
+
+            match self.directory_index(&req_path).await {
+                Some(index_path) => {
+                    if !req_path.ends_with('/') && !req_path.is_empty() {
+                        self.redirect_to_directory(&req_path, response);
+                        return;
+                    }
+                    meta = self.fs.stat(&index_path).await.unwrap_or(None);
+                    req_path = index_path;
+                }
+                None => {
+                    if self.options.listing_allowed(&req_path) {
+                        self.handle_directory_listing(&req_path, request, response).await;
+                    } else if let Some(status) = self.options.listing_disabled_status() {
+                        // Rocket's 404 page would tell otherwise
+                        response.set_status(status);
+                        response.remove_header("Content-Type");
+                        response.set_sized_body(0, Cursor::new(""));
+                    } else {
+                        self.not_found(response);
+                    }
+                    return;
+                }
+            }
+        }
+
+        // A language variant is read instead of the file for clients preferring its language.
+        let mut negotiated_language = false;
+        if let (true, false, Some(FileMeta::File(..))) =
+            (self.options.language_negotiation(), gzip_only, &meta)
+        {
+            if let Some((variant, variant_meta)) = self.language_variant(request, &req_path).await {
+                req_path = variant;
+                meta = Some(variant_meta);
+                negotiated_language = true;
+            }
+        }
+
+        let (size, last_modified) = match meta {
+            Some(FileMeta::File(size, last_modified)) => (size, last_modified),
+            _ => {
+                self.not_found(response);
+                return;
+            }
+        };
+
+        // Source maps may be kept to some origins, for everyone else they don't exist. This
+        // only looks at the resolved file, so extensions and index files can't get around it.
+        if is_source_map(&req_path) && !self.source_maps_allowed(request) {
+            self.not_found(response);
+            return;
+        }
+
+        // The checksum is of the file as it's stored, so gzip only assets have none
+        if self.options.allow_checksum_query() && !gzip_only {
+            if let Some(algorithm) = request.query_value::<&str>("checksum").and_then(Result::ok) {
+                self.handle_checksum(&req_path, algorithm, response).await;
+                return;
+            }
+        }
+
+        // Let's set the mime type here, this can't possibly go wrong anymore *cough*.
+        let essence = {
+            // Extensions match in any case, so `photo.JPG` is an image just like `photo.jpg`
+            let extension = Path::new(&req_path)
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(str::to_ascii_lowercase);
+            let modern = MODERN_MIME_TYPES
+                .iter()
+                .find(|(modern, _)| Some(*modern) == extension.as_deref());
+            let guessed = extension.as_deref().and_then(|e| mime_guess::from_ext(e).first());
+            let mut mime = match (modern, guessed) {
+                (Some((_, mime)), _) => mime.to_string(),
+                (None, Some(mime)) => mime.to_string(),
+                (None, None) => self.options.default_content_type().to_string(),
+            };
+            let essence = mime.split(';').next().unwrap_or("").trim().to_string();
+            if !mime.contains("charset=") {
+                if let Some(charset) = self.options.charset_for(&essence) {
+                    mime = format!("{}; charset={}", mime, charset);
+                }
+            }
+            response.set_header(Header::new("Content-Type", mime));
+
+            match self.options.disposition_for(&essence) {
+                Some(Disposition::Inline) => {
+                    response.set_header(Header::new("Content-Disposition", "inline"));
+                }
+                Some(Disposition::Attachment) => {
+                    let name = req_path.rsplit('/').next().unwrap_or(&req_path);
+                    response.set_header(Header::new("Content-Disposition", attachment(name)));
+                }
+                None => {}
+            }
+            essence
+        };
+
+        let ranges_disabled = self
+            .options
+            .no_range_pattern()
+            .is_some_and(|pattern| pattern.is_match(uri));
+        // Ranges are in terms of the uncompressed bytes, so ranged requests read the file itself
+        let ranged =
+            request.headers().contains("Range") && self.fs.supports_ranges() && !ranges_disabled;
+
+        // A precompressed sibling is read instead of the file if the client accepts it, except
+        // for pages getting a banner, which only goes into the uncompressed file, and ranges.
+        let banner_page = essence == "text/html" && self.options.html_banner().is_some();
+        let variant = if self.options.precompressed_variants()
+            && !gzip_only
+            && !banner_page
+            && !ranged
+        {
+            self.precompressed_variant(request, &req_path).await
+        } else {
+            None
+        };
+
+        // The file we actually read, which differs from the requested one for gzip only assets
+        // and precompressed variants, and the encoding its bytes are in. Gzip only assets are
+        // decoded again for clients not accepting gzip.
+        let (precompressed, file_path, size, last_modified) = match variant {
+            Some((encoding, path, size, modified)) => (Some(encoding), path, size, modified),
+            None if gzip_only => (Some("gzip"), gzip_path, size, last_modified),
+            None => (None, req_path.clone(), size, last_modified),
+        };
+        let decode_gzip = gzip_only && !accepts_gzip;
+
+        if self.options.content_language_from_filename() || negotiated_language {
+            if let Some(language) = filename_language(&req_path) {
+                response.set_header(Header::new("Content-Language", language.to_string()));
+            }
+        }
+
+        let no_store = self
+            .options
+            .no_store_pattern()
+            .is_some_and(|pattern| pattern.is_match(uri));
+        if no_store {
+            response.set_header(Header::new("Cache-Control", "no-store"));
+        } else if let Some(immutable_prefix) = self.options.immutable_prefix() {
+            if uri.starts_with(immutable_prefix.as_str()) {
+                response.set_header(Header::new(
+                    "Cache-Control",
+                    "public, max-age=31536000, immutable",
+                ));
+            }
+        }
+
+        // Get the file modification date, unless disabled, and the If-Modified-Since header value.
+        // Files which must not be stored don't get a validator to revalidate with either.
+        let modified: Option<DateTime<Utc>> = if no_store || self.options.disable_last_modified() {
+            None
+        } else {
+            // Dates in headers only have second precision, so we compare in whole seconds
+            // on both sides.
+            Some(DateTime::<Utc>::from(last_modified).trunc_subsecs(0))
+        };
+        let if_modified_since = request.headers().get("If-Modified-Since").next();
+
+        // The ETag changes along with the file's size or modification date, and the query if
+        // enabled. Responses whose bytes differ from the file's get it as a weak one further
+        // down.
+        let etag = if no_store {
+            None
+        } else {
+            let mtime = last_modified
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|mtime| mtime.as_secs())
+                .unwrap_or(0);
+            let tag = match request.uri().query().filter(|_| self.options.query_in_etag()) {
+                Some(query) => {
+                    let query = crc32fast::hash(query.as_str().as_bytes());
+                    format!("\"{:x}-{:x}-{:x}\"", mtime, size, query)
+                }
+                None => format!("\"{:x}-{:x}\"", mtime, size),
+            };
+            // Pages change along with their banner, so caches have to revalidate them then
+            let tag = match self.options.html_banner().filter(|_| banner_page) {
+                Some(banner) => {
+                    let banner = crc32fast::hash(banner.as_bytes());
+                    format!("{}-{:x}\"", tag.trim_end_matches('"'), banner)
+                }
+                None => tag,
+            };
+            match self.options.etag_strength() {
+                Some(EtagStrength::Weak) => Some(format!("W/{}", tag)),
+                _ => Some(tag),
+            }
+        };
+
+        // The validators go on every response for the file, including a 304, so caches can
+        // update what they've stored.
+        if let Some(modified) = modified {
+            response.set_header(Header::new(
+                "Last-Modified",
+                modified.format(LAST_MODIFIED_DATE_FORMAT).to_string(),
+            ));
+        }
+        if let Some(ref etag) = etag {
+            response.set_header(Header::new("ETag", etag.clone()));
+        }
+        if precompressed.is_some() || self.options.precompressed_variants() {
+            add_vary(response, "Accept-Encoding");
+        }
+        if self.options.language_negotiation() {
+            add_vary(response, "Accept-Language");
+        }
+
+        // Clients asking for a version of the file other than the current one get a 412.
+        // If-Match always compares strongly, so only `*` matches weak ETags.
+        if let Some(if_match) = request.headers().get_one("If-Match") {
+            let matches = if_match.trim() == "*"
+                || etag.as_ref().is_some_and(|etag| etag_matches(if_match, etag, true));
+            if !matches {
+                response.set_status(Status::PreconditionFailed);
+                response.set_sized_body(0, Cursor::new(""));
+                return;
+            }
+        }
+
+        // If the client's copy is still current, we respond with a 304 here, to HEAD requests
+        // too since caches revalidate with them. If-None-Match takes precedence over
+        // If-Modified-Since if both are given.
+        let not_modified = match (request.headers().get_one("If-None-Match"), &etag) {
+            (Some(if_none_match), Some(etag)) => etag_matches(
+                if_none_match,
+                etag,
+                self.options.etag_strength() == Some(EtagStrength::Strong),
+            ),
+            (Some(_), None) => false,
+            (None, _) => match (modified, if_modified_since) {
+                (Some(modified), Some(time)) => {
+                    NaiveDateTime::parse_from_str(time, LAST_MODIFIED_DATE_FORMAT)
+                        .is_ok_and(|time| time.and_utc() == modified)
+                }
+                _ => false,
+            },
+        };
+        if not_modified {
+            response.set_status(Status::NotModified);
+            return;
+        }
+
+        // The banner only goes into complete HTML responses, ranges are cut from the file as is
+        let banner = self
+            .options
+            .html_banner()
+            .filter(|_| essence == "text/html" && !request.headers().contains("Range"));
+
+        // Transformed files are read completely, their length is only known afterwards.
+        let transform = self.options.transform();
+        if precompressed.is_none() && (transform.is_some() || banner.is_some()) {
+            // Finding out the length would take reading the file, which HEAD never does
+            if method == Method::Head {
+                response.set_status(Status::Ok);
+                weaken_etag(response, &etag);
+                response.set_header(Header::new("Accept-Ranges", "none"));
+                response.set_streamed_body(tokio::io::empty());
+                return;
+            }
+            let _permit = match self.open_permit() {
+                Ok(permit) => permit,
+                Err(_) => {
+                    response.set_status(Status::ServiceUnavailable);
+                    return;
+                }
+            };
+            let mut f = match self.fs.open(&file_path, None).await {
+                Ok(f) => f,
+                Err(err) => {
+                    filesystem_error(response, &*err);
+                    return;
+                }
+            };
+            let mut bytes = Vec::with_capacity(size as usize);
+            if let Err(err) = f.read_to_end(&mut bytes).await {
+                filesystem_error(response, &err);
+                return;
+            }
+
+            let mut body = match transform {
+                Some(transform) => transform(&req_path, bytes),
+                None => bytes,
+            };
+            if let Some(banner) = banner {
+                body = inject_banner(body, banner);
+            }
+            response.set_status(Status::Ok);
+            weaken_etag(response, &etag);
+            response.set_header(Header::new("Accept-Ranges", "none"));
+            response.set_sized_body(body.len(), Cursor::new(body));
+            return;
+        }
+
+        let accept_ranges = if self.fs.supports_ranges() && !ranges_disabled {
+            "bytes"
+        } else {
+            "none"
+        };
+
+        // In case someone heads the file, we inform him about the content length and
+        // whether we support byte ranges.
+        if method == Method::Head {
+            // Rocket strips the body anyway, but a sized one would bring its own length.
+            response.set_streamed_body(tokio::io::empty());
+            if let Some(encoding) = precompressed {
+                if decode_gzip {
+                    weaken_etag(response, &etag);
+                } else {
+                    response.set_header(Header::new("Content-Encoding", encoding));
+                    response.set_header(Header::new("Content-Length", format!("{}", size)));
+                }
+            } else {
+                response.set_header(Header::new("Accept-Ranges", accept_ranges));
+                // A GET would be compressed just like below, so its length isn't the file size
+                if self.compressible(size) {
+                    add_vary(response, "Accept-Encoding");
+                }
+                match self.live_encoding(request, size) {
+                    Some(encoding) => {
+                        response.set_header(Header::new("Content-Encoding", encoding));
+                        weaken_etag(response, &etag);
+                    }
+                    None => {
+                        response.set_header(Header::new("Content-Length", format!("{}", size)));
+                    }
+                }
+            }
+            response.set_status(Status::Ok);
+            return;
+        }
+
+        // Let's parse the range header if it exists
+        let range_header = request.headers().get_one("Range").unwrap_or("");
+
+        // A range with If-Range only applies if the file is unchanged since the given date,
+        // otherwise the client needs the complete new file.
+        let if_range_matches = match request.headers().get_one("If-Range") {
+            Some(time) => {
+                let time = NaiveDateTime::parse_from_str(time, LAST_MODIFIED_DATE_FORMAT);
+                match (modified, time) {
+                    (Some(modified), Ok(time)) => time.and_utc() == modified,
+                    _ => false,
+                }
+            }
+            None => true,
+        };
+
+        // If we get a multipart range request, we more or less fail gracefully here for the moment.
+        // We simply send the complete file for it, see Range::single.
+        // TODO: Support multipart ranges
+        // Ranges of precompressed files are ignored as well, since the bytes we read don't
+        // match the representation the client asked for.
+        let range: Result<Range, Box<dyn StdError + Send + Sync + 'static>> =
+            if precompressed.is_some() {
+                Err(Box::new(Error::new("ranges of precompressed files not supported")))
+            } else if !self.fs.supports_ranges() {
+                Err(Box::new(Error::new("ranges not supported by the filesystem")))
+            } else if ranges_disabled {
+                Err(Box::new(Error::new("ranges disabled for the path")))
+            } else if !if_range_matches {
+                Err(Box::new(Error::new("file changed since the If-Range date")))
+            } else {
+                range_header.parse::<Range>()
+            };
+
+        // Resolve the range to the first and last byte we send, and set the start byte for the
+        // request. A single range starting past the end can't be satisfied, and no range is ever
+        // satisfiable for an empty file, which has no whole file to send instead either.
+        let range = match range {
+            Ok(_) if size == 0 => Some(None),
+            Ok(range) => range.single().map(|range| range.resolve(size)),
+            Err(_) => None,
+        };
+        if let Some(None) = range {
+            response.set_status(Status::RangeNotSatisfiable);
+            response.set_header(Header::new("Accept-Ranges", accept_ranges));
+            response.set_header(Header::new("Content-Range", format!("bytes */{}", size)));
+            response.set_sized_body(0, Cursor::new(""));
+            return;
+        }
+        let range = range.flatten();
+        // Otherwise we try to send the file, which should work since that size above should have
+        // worked as well.
+        // Only ranges get an offset, which backends which can't seek never see, see
+        // FileSystem::open.
+        let offset = range.map(|(start, _)| start);
+        let start = offset.unwrap_or(0);
+        let permit = match self.open_permit() {
+            Ok(permit) => permit,
+            Err(_) => {
+                response.set_status(Status::ServiceUnavailable);
+                return;
+            }
+        };
+        let f = match self.fs.open(&file_path, offset).await {
+            Ok(f) => f,
+            Err(err) => {
+                filesystem_error(response, &*err);
+                return;
+            }
+        };
+
+        response.set_status(Status::Ok);
+
+        // We shadow and box our f here to support different Read implementations
+        let mut f: Box<dyn AsyncRead + Send + Unpin> = Box::new(f);
+
+        if let Some(permit) = permit {
+            f = Box::new(PermitReader::new(f, permit));
+        }
+
+        if let Some(warning) = self.options.slow_file_warning() {
+            f = Box::new(TimedReader::new(f, &req_path, started, warning.clone()));
+        }
+
+        // Precompressed files are already encoded, so they skip any further encoding.
+        if let Some(encoding) = precompressed {
+            if !decode_gzip {
+                response.set_header(Header::new("Content-Encoding", encoding));
+                response.set_streamed_body(f);
+                return;
+            }
+
+            #[cfg(feature = "content_encoding")]
+            {
+                weaken_etag(response, &etag);
+                response.set_streamed_body(GzipDecoder::new(BufReader::new(f)));
+            }
+            return;
+        }
+
+        response.set_header(Header::new("Accept-Ranges", accept_ranges));
+
+        let mut body_length = size;
+
+        // If we got a range header, we set the corresponding headers here and
+        // set f to a limit reader so it will stop when it reached the range len.
+        if let Some((start, end)) = range {
+            let content_length = end - start + 1;
+            if content_length < size - start {
+                f = Box::new(f.take(content_length));
+            }
+            body_length = content_length;
+            response
+                .set_header(Header::new("Content-Length", format!("{}", content_length)));
+            response.set_header(Header::new(
+                "Content-Range",
+                format!("bytes {}-{}/{}", start, end, size),
+            ));
+            response.set_status(Status::PartialContent);
+        }
+
+        #[cfg(feature = "content_encoding")]
+        {
+            // In case the client accepts encodings, we handle these. The encoded length
+            // isn't known upfront, so we can't keep a Content-Length around.
+            if self.compressible(body_length) {
+                add_vary(response, "Accept-Encoding");
+            }
+            if let Some(encoding) = self.live_encoding(request, body_length) {
+                response.remove_header("Content-Length");
+                response.set_header(Header::new("Content-Encoding", encoding));
+                weaken_etag(response, &etag);
+                if encoding == "gzip" {
+                    response.set_streamed_body(GzipEncoder::new(BufReader::new(f)));
+                } else {
+                    response.set_streamed_body(DeflateEncoder::new(BufReader::new(f)));
+                }
+                return;
+            }
+        }
+
+        // Streamed bodies go without a length, which for empty files we know to be 0.
+        if body_length == 0 {
+            response.set_sized_body(0, Cursor::new(""));
+            return;
+        }
+
+        // Embedded files are in memory anyway, so we hand out the exact slice instead of
+        // streaming it.
+        if let Some(bytes) = self.fs.static_bytes(&file_path) {
+            if let Some(slice) = bytes.get(start as usize..(start + body_length) as usize) {
+                response.remove_header("Content-Length");
+                response.set_sized_body(slice.len(), Cursor::new(slice));
+                return;
+            }
+        }
+
+        if let Some(threshold) = self.options.small_file_threshold() {
+            if body_length < threshold as u64 {
+                let mut buf = Vec::with_capacity(body_length as usize);
+                if let Err(err) = f.read_to_end(&mut buf).await {
+                    filesystem_error(response, &err);
+                    return;
+                }
+                response.remove_header("Content-Length");
+                response.set_sized_body(buf.len(), Cursor::new(buf));
+                return;
+            }
+        }
+
+        response.set_streamed_body(f);
+    }
+}
+
+#[rocket::async_trait]
+impl<T: 'static> Fairing for StaticFileServer<T>
+where
+    T: FileSystem + Sized + Send + Sync,
+{
+    fn info(&self) -> Info {
+        Info {
+            name: "static_file_server",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let method = request.method();
+        request.local_cache(|| RequestMethod(method));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let started = Instant::now();
+
+        // Only handle requests which aren't otherwise handled.
+        if response.status() != Status::NotFound {
+            return;
+        }
+
+        // Only handle requests which include our prefix
+        let method = request.local_cache(|| RequestMethod(request.method())).0;
+        let uri = request.uri().path().to_string();
+        let prefix = self.options.prefix();
+        let is_root = uri == prefix.trim_end_matches('/');
+        if !((method == Method::Get || method == Method::Head)
+            && (uri.starts_with(prefix) || is_root))
+        {
+            return;
+        }
+
+        self.serve(request, response, method, &uri, is_root, started).await;
+
+        if let Some(bytes_per_sec) = self.options.throttle_bytes_per_sec() {
+            wrap_body(response, |body| ThrottledReader::new(body, bytes_per_sec));
+        }
+
+        // The body is counted as it's sent, see OptionsBuilder::on_bytes_sent
+        if let Some(callback) = self.options.bytes_sent_callback() {
+            if !wrap_body(response, |body| CountingReader::new(body, &uri, callback.clone())) {
+                callback(&uri, 0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(unused)]
+    use super::fs::*;
+    use super::*;
+    use regex::Regex;
+    use rocket::http::{Header, Status};
+    use rocket::local::blocking::Client;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_with_local_filesystem() {
+        let fs = LocalFileSystem::new("src");
+        let options = OptionsBuilder::new().prefix("/test").into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        // Test simply getting a file
+        let resp = client.get("/test/lib.rs").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(
+            resp.headers()
+                .get_one("Content-Type")
+                .expect("no content type"),
+            "text/x-rust"
+        );
+
+        let last_modified = resp.headers()
+            .get_one("Last-Modified")
+            .expect("no last modified header")
+            .to_owned();
+
+        // Check for NotModified on second response with If-Modified-Since header
+        let resp = client
+            .get("/test/lib.rs")
+            .header(Header::new("If-Modified-Since", last_modified))
+            .dispatch();
+        assert_eq!(resp.status(), Status::NotModified);
+
+        // Test for Range support
+        let resp = client
+            .get("/test/lib.rs")
+            .header(Header::new("Range", "bytes=5-10"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PartialContent);
+        assert_eq!(resp.headers().get_one("Content-Length"), Some("6"));
+        // A single range keeps the file's type, multipart/byteranges is for several ones
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/x-rust"));
+        let body = resp.into_bytes().unwrap();
+        assert_eq!(body.len(), 6);
+
+        let resp = client
+            .get("/test/lib.rs")
+            .header(Header::new("Range", "bytes=0-1,5-10"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/x-rust"));
+    }
+
+    #[test]
+    fn test_with_embedded_filesystem() {
+        let bytes = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/target/test.package"));
+        let fs = EmbeddedFileSystem::from_bytes(bytes);
+
+        match fs {
+            Ok(fs) => {
+                let options = OptionsBuilder::new().prefix("/test").into();
+                let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+                let client = Client::tracked(rocket).expect("valid rocket");
+
+                let resp = client.get("/test/hello.txt").dispatch();
+                assert_eq!(resp.status(), Status::Ok);
+                assert_eq!(
+                    resp.headers()
+                        .get_one("Content-Type")
+                        .expect("no content type"),
+                    "text/plain"
+                );
+
+                let last_modified = resp.headers()
+                    .get_one("Last-Modified")
+                    .expect("no last modified header")
+                    .to_owned();
+                assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+
+                // Check for NotModified on second response with If-Modified-Since header
+                let resp = client
+                    .get("/test/hello.txt")
+                    .header(Header::new("If-Modified-Since", last_modified))
+                    .dispatch();
+                assert_eq!(resp.status(), Status::NotModified);
+
+                // Test for Range support
+                let resp = client
+                    .get("/test/hello.txt")
+                    .header(Header::new("Range", "bytes=5-10"))
+                    .dispatch();
+                assert_eq!(resp.status(), Status::PartialContent);
+                // Embedded bodies are sized, Rocket sends their Content-Length itself
+                assert_eq!(resp.body().preset_size(), Some(6));
+                assert_eq!(resp.into_string(), Some(" World".to_string()));
+
+                let resp = client
+                    .get("/test/hello.txt")
+                    .header(Header::new("Range", "bytes=0-"))
+                    .dispatch();
+                assert_eq!(resp.status(), Status::PartialContent);
+                assert_eq!(resp.into_bytes().unwrap().len(), 12);
+            }
+            Err(e) => panic!(
+                "unable to load test.package, maybe you just need to re-run the test: {}",
+                e
+            ),
+        }
+    }
+
+    #[test]
+    fn test_with_owned_embedded_filesystem() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/target/test.package");
+        let fs = EmbeddedFileSystem::from_vec(std::fs::read(path).unwrap()).expect("valid package");
+        assert!(fs.static_bytes("hello.txt").is_none());
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("Range", "bytes=6-10"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PartialContent);
+        assert_eq!(resp.into_string(), Some("World".to_string()));
+
+        let file = std::fs::File::open(path).unwrap();
+        let fs = EmbeddedFileSystem::from_reader(file).expect("valid package");
+        assert_eq!(fs.paths().len(), 5);
+    }
+
+    #[test]
+    fn test_with_shared_filesystem() {
+        let bytes = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/target/test.package"));
+        let fs = Arc::new(EmbeddedFileSystem::from_bytes(bytes).expect("valid package"));
+
+        let first = OptionsBuilder::new().prefix("/first").into();
+        let second = OptionsBuilder::new().prefix("/second").into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(Arc::clone(&fs), first).unwrap())
+            .attach(StaticFileServer::new(Arc::clone(&fs), second).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        assert_eq!(Arc::strong_count(&fs), 3);
+
+        let resp = client.get("/first/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+
+        let resp = client.get("/second/inner/other.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+
+        let resp = client.get("/third/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_maintenance() {
+        let fs = LocalFileSystem::new("src");
+        let maintenance = MaintenanceConfig::new(120).page("<h1>Back soon</h1>");
+        let options = OptionsBuilder::new()
+            .prefix("/test")
+            .maintenance(Some(maintenance))
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/test/lib.rs").dispatch();
+        assert_eq!(resp.status(), Status::ServiceUnavailable);
+        assert_eq!(resp.headers().get_one("Retry-After"), Some("120"));
+        assert_eq!(resp.into_string(), Some("<h1>Back soon</h1>".to_string()));
+
+        let fs = LocalFileSystem::new("src");
+        let options = OptionsBuilder::new()
+            .prefix("/test")
+            .maintenance(None)
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/test/lib.rs").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.headers().get_one("Retry-After").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "content_encoding")]
+    fn test_gzip_only_assets() {
+        let fs = LocalFileSystem::new("testdata/gzip");
+        let options = OptionsBuilder::new().gzip_only_assets(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        // Clients without gzip support get the decompressed asset
+        let resp = client.get("/app.js").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/javascript"));
+        assert!(resp.headers().get_one("Content-Encoding").is_none());
+        assert_eq!(
+            resp.into_string(),
+            Some("console.log(\"Hello World!\");\n".to_string())
+        );
+
+        // Clients with gzip support get the stored bytes
+        let resp = client
+            .get("/app.js")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/javascript"));
+        assert_eq!(resp.headers().get_one("Content-Encoding"), Some("gzip"));
+        let stored = std::fs::read("testdata/gzip/app.js.gz").unwrap();
+        assert_eq!(resp.into_bytes(), Some(stored));
+
+        // Refusing gzip with a quality of 0 is the same as not mentioning it
+        let resp = client
+            .get("/app.js")
+            .header(Header::new("Accept-Encoding", "gzip;q=0, identity"))
+            .dispatch();
+        assert!(resp.headers().get_one("Content-Encoding").is_none());
+        assert_eq!(
+            resp.into_string(),
+            Some("console.log(\"Hello World!\");\n".to_string())
+        );
+
+        // Without the option the asset doesn't exist
+        let fs = LocalFileSystem::new("testdata/gzip");
+        let options = OptionsBuilder::new().into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let resp = client.get("/app.js").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_try_extensions() {
+        let fs = LocalFileSystem::new("testdata/site");
+        let options = OptionsBuilder::new()
+            .try_extensions(vec![".htm".to_string(), ".html".to_string()])
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/about").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/html"));
+        assert!(resp.into_string().unwrap().contains("<h1>About</h1>"));
+
+        let resp = client.get("/about.html").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+
+        let resp = client.get("/contact").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_max_path_depth() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let options = OptionsBuilder::new().max_path_depth(2).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/inner/other.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+
+        let resp = client.get("/inner/deeper/deep.txt").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_max_path_length() {
+        let fs = MockFileSystem::new("testdata/assets");
+        let stats = fs.stats.clone();
+        let options = OptionsBuilder::new().max_path_length(16).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/inner/other.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+
+        let stats_before = stats.load(Ordering::SeqCst);
+        let resp = client.get(format!("/{}.txt", "a".repeat(100))).dispatch();
+        assert_eq!(resp.status(), Status::UriTooLong);
+        assert_eq!(stats.load(Ordering::SeqCst), stats_before);
+    }
+
+    #[test]
+    fn test_stream_directory_listing() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/streamed-listing");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        for i in 0..200 {
+            std::fs::write(dir.join(format!("file{:03}.txt", i)), "x").unwrap();
+        }
+
+        let listing = |stream: bool| {
+            let options = OptionsBuilder::new()
+                .allow_directory_listing(true)
+                .stream_directory_listing(stream)
+                .into();
+            let rocket = rocket::build()
+                .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+            let client = Client::tracked(rocket).expect("valid rocket");
+            let resp = client.get("/").dispatch();
+            assert_eq!(resp.status(), Status::Ok);
+            assert_eq!(resp.headers().get_one("Content-Type"), Some("text/html; charset=utf-8"));
+            assert_eq!(resp.body().preset_size().is_none(), stream);
+            resp.into_string().unwrap()
+        };
+
+        let body = listing(true);
+        assert!(body.starts_with("<!DOCTYPE html>"));
+        assert!(body.trim_end().ends_with("</html>"));
+        assert_eq!(body.matches("<tr").count(), 1 + 201);
+        assert_eq!(body.matches("</tr>").count(), 1 + 201);
+        assert!(body.contains(r#"href="sub/""#));
+        for i in 0..200 {
+            assert!(body.contains(&format!(r#"href="file{:03}.txt""#, i)));
+        }
+        let tbody = body.find("<tbody>").unwrap();
+        assert!(tbody < body.find("file000.txt").unwrap());
+        assert!(body.find("file199.txt").unwrap() < body.find("</tbody>").unwrap());
+
+        // The same rows as rendered at once
+        let rows = |body: &str| -> Vec<String> {
+            body.split("<tr").skip(1).map(|row| row.split_whitespace().collect()).collect()
+        };
+        assert_eq!(rows(&body), rows(&listing(false)));
+    }
+
+    #[test]
+    fn test_missing_root() {
+        let fs = LocalFileSystem::new("testdata/missing");
+        let err = StaticFileServer::new(fs, Options::default()).err().unwrap();
+        assert!(err.to_string().starts_with("can't serve testdata/missing: "));
+
+        // Every root has to be there, not only the first one
+        let roots = vec!["testdata/assets".into(), "testdata/missing".into()];
+        let fs = LocalFileSystem::with_roots(roots);
+        assert!(StaticFileServer::new(fs, Options::default()).is_err());
+
+        let fs = LocalFileSystem::new("testdata/assets/hello.txt");
+        assert!(StaticFileServer::new(fs, Options::default()).is_err());
+        let fs = LocalFileSystem::new("testdata/assets");
+        assert!(StaticFileServer::new(fs, Options::default()).is_ok());
+    }
+
+    #[test]
+    fn test_directory_listing_with_local_filesystem() {
+        let fs = LocalFileSystem::new("");
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/src").dispatch();
+        assert_eq!(resp.status(), Status::Found);
+        assert_eq!(resp.headers().get_one("Location"), Some("/src/"));
+
+        let resp = client.get("/src/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.into_string().unwrap();
+        assert!(body.contains(r#"href="lib.rs""#));
+    }
+
+    #[test]
+    fn test_directory_index_with_local_filesystem() {
+        let fs = LocalFileSystem::new("testdata/site");
+        let options = OptionsBuilder::new()
+            .directory_listing_default_index("index.html")
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/docs").dispatch();
+        assert_eq!(resp.status(), Status::Found);
+        assert_eq!(resp.headers().get_one("Location"), Some("/docs/"));
+
+        let resp = client.get("/docs/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/html"));
+        let body = resp.into_string().unwrap();
+        assert!(body.contains(r#"href="guide.html""#));
+    }
+
+    #[test]
+    fn test_precompressed_variants() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/precompressed-variants");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("app.js"), "plain").unwrap();
+        std::fs::write(dir.join("app.js.br"), "brotli").unwrap();
+        std::fs::write(dir.join("app.js.gz"), "gzipped").unwrap();
+        std::fs::write(dir.join("style.css"), "plain").unwrap();
+        std::fs::write(dir.join("style.css.gz"), "gzipped").unwrap();
+
+        let options = OptionsBuilder::new().precompressed_variants(true).into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let get = |path: &'static str, accept_encoding: &'static str| {
+            let resp = client
+                .get(path)
+                .header(Header::new("Accept-Encoding", accept_encoding))
+                .dispatch();
+            assert_eq!(resp.status(), Status::Ok);
+            assert_eq!(resp.headers().get_one("Vary"), Some("Accept-Encoding"));
+            let encoding = resp.headers().get_one("Content-Encoding").map(str::to_string);
+            (encoding, resp.into_string().unwrap())
+        };
+
+        assert_eq!(get("/app.js", "gzip, br"), (Some("br".to_string()), "brotli".to_string()));
+        assert_eq!(get("/app.js", "gzip"), (Some("gzip".to_string()), "gzipped".to_string()));
+        assert_eq!(
+            get("/app.js", "br;q=0.5, gzip"),
+            (Some("gzip".to_string()), "gzipped".to_string())
+        );
+        assert_eq!(
+            get("/app.js", "br;q=0, *"),
+            (Some("gzip".to_string()), "gzipped".to_string())
+        );
+        assert_eq!(get("/app.js", "identity"), (None, "plain".to_string()));
+        // Only the variants which exist are considered
+        assert_eq!(
+            get("/style.css", "br, gzip"),
+            (Some("gzip".to_string()), "gzipped".to_string())
+        );
+
+        let resp = client
+            .get("/app.js")
+            .header(Header::new("Accept-Encoding", "br"))
+            .dispatch();
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/javascript"));
+        let etag = resp.headers().get_one("ETag").unwrap().to_string();
+        let resp = client.get("/app.js").dispatch();
+        assert_ne!(resp.headers().get_one("ETag"), Some(etag.as_str()));
+
+        let resp = client
+            .head("/app.js")
+            .header(Header::new("Accept-Encoding", "br, gzip"))
+            .dispatch();
+        assert_eq!(resp.headers().get_one("Content-Encoding"), Some("br"));
+        assert_eq!(resp.headers().get_one("Content-Length"), Some("6"));
+
+        // Without the option the variants are just other files
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let resp = client
+            .get("/app.js")
+            .header(Header::new("Accept-Encoding", "br"))
+            .dispatch();
+        assert!(resp.headers().get_one("Content-Encoding").is_none());
+        assert_eq!(resp.into_string(), Some("plain".to_string()));
+    }
+
+    #[cfg(feature = "content_encoding")]
+    #[test]
+    fn test_package_with_gzip_variants() {
+        use std::io::Write;
+
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/gzip-package");
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = "console.log('hello');\n".repeat(50);
+        std::fs::write(dir.join("app.js"), &script).unwrap();
+        std::fs::write(dir.join("tiny.txt"), "tiny").unwrap();
+
+        let mut package = Vec::new();
+        create_package_from_dir_with_gzip(&dir, &mut package).expect("unable to create package");
+        let fs = EmbeddedFileSystem::from_vec(package).expect("valid package");
+        assert!(fs.validate().is_ok());
+        // The variants aren't files of their own
+        assert_eq!(fs.paths(), vec!["app.js", "tiny.txt"]);
+
+        let options = OptionsBuilder::new()
+            .precompressed_variants(true)
+            .allow_directory_listing(true)
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client
+            .get("/app.js")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Encoding"), Some("gzip"));
+        let mut encoder =
+            flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(script.as_bytes()).unwrap();
+        assert_eq!(resp.into_bytes(), Some(encoder.finish().unwrap()));
+
+        // Ranges are served from the uncompressed file, even to clients accepting gzip
+        let resp = client
+            .get("/app.js")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .header(Header::new("Range", "bytes=1078-"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PartialContent);
+        assert!(resp.headers().get_one("Content-Encoding").is_none());
+        assert_eq!(resp.headers().get_one("Content-Range"), Some("bytes 1078-1099/1100"));
+        assert_eq!(resp.into_string(), Some("console.log('hello');\n".to_string()));
+
+        let resp = client.get("/app.js").dispatch();
+        assert!(resp.headers().get_one("Content-Encoding").is_none());
+        assert_eq!(resp.into_string(), Some(script));
+
+        // Files gzip doesn't make smaller are stored without a variant
+        let resp = client
+            .get("/tiny.txt")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert!(resp.headers().get_one("Content-Encoding").is_none());
+        assert_eq!(resp.into_string(), Some("tiny".to_string()));
+
+        let body = client.get("/").dispatch().into_string().unwrap();
+        assert!(body.contains(r#"href="app.js""#));
+        assert!(!body.contains("app.js.gz"));
+    }
+
+    #[test]
+    fn test_directory_redirect_loop() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/redirect-loop");
+        std::fs::create_dir_all(dir.join("docs")).unwrap();
+        std::fs::write(dir.join("docs/index.html"), "index").unwrap();
+        std::fs::create_dir_all(dir.join("list")).unwrap();
+
+        for normalize in [false, true] {
+            let options = OptionsBuilder::new()
+                .prefix("/static")
+                .allow_directory_listing(true)
+                .directory_listing_default_index("index.html")
+                .normalize_backslashes(normalize)
+                .into();
+            let rocket = rocket::build()
+                .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+            let client = Client::tracked(rocket).expect("valid rocket");
+
+            for path in ["/static/docs", "/static/list", "/static/do%63s", "/static/%6Cist"] {
+                let resp = client.get(path).dispatch();
+                assert_eq!(resp.status(), Status::Found, "{}", path);
+                let location = resp.headers().get_one("Location").unwrap().to_string();
+                assert_ne!(location, path);
+
+                // Following the redirect serves the directory instead of redirecting again
+                let resp = client.get(location.clone()).dispatch();
+                assert_eq!(resp.status(), Status::Ok, "{}", location);
+                assert!(resp.headers().get_one("Location").is_none());
+            }
+
+            // Slashes spelled out in the request still count as the trailing slash
+            let mut paths = vec!["/static/docs%2F", "/static/list%2F"];
+            if normalize {
+                paths.extend(["/static/docs%5C", "/static/list%5C"]);
+            }
+            for path in paths {
+                let resp = client.get(path).dispatch();
+                assert_eq!(resp.status(), Status::Ok, "{}", path);
+                assert!(resp.headers().get_one("Location").is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_full_response_opens_without_start() {
+        let fs = MockFileSystem::new("testdata/assets");
+        let seeking_opens = fs.seeking_opens.clone();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+        assert_eq!(seeking_opens.load(Ordering::SeqCst), 0);
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("Range", "bytes=6-"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("World!".to_string()));
+        assert_eq!(seeking_opens.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/empty-file");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("empty.txt"), "").unwrap();
+
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/empty.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.body().preset_size(), Some(0));
+        assert_eq!(resp.into_bytes(), Some(Vec::new()));
+
+        for range in &["bytes=0-0", "bytes=0-", "bytes=-5", "bytes=3-9"] {
+            let resp = client
+                .get("/empty.txt")
+                .header(Header::new("Range", *range))
+                .dispatch();
+            assert_eq!(resp.status(), Status::RangeNotSatisfiable, "{}", range);
+            assert_eq!(resp.headers().get_one("Content-Range"), Some("bytes */0"));
+            assert_eq!(resp.into_bytes(), Some(Vec::new()));
+        }
+
+        // Invalid range headers are ignored as usual
+        let resp = client
+            .get("/empty.txt")
+            .header(Header::new("Range", "bytes=x"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_single_byte_probe() {
+        let probe = |fs: MockFileSystem, options: Options| {
+            let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+            let client = Client::tracked(rocket).expect("valid rocket");
+            let resp = client
+                .get("/hello.txt")
+                .header(Header::new("Range", "bytes=0-0"))
+                .dispatch();
+            let header = |name| resp.headers().get_one(name).map(str::to_string);
+            let headers = (
+                header("Accept-Ranges"),
+                header("Content-Range"),
+                header("Content-Length"),
+            );
+            (resp.status(), headers, resp.into_string().unwrap())
+        };
+
+        let fs = MockFileSystem::new("testdata/assets");
+        let (status, headers, body) = probe(fs, Options::default());
+        assert_eq!(status, Status::PartialContent);
+        assert_eq!(
+            headers,
+            (Some("bytes".to_string()), Some("bytes 0-0/12".to_string()), Some("1".to_string()))
+        );
+        assert_eq!(body, "H");
+
+        // Without range support the probe gets the whole file and learns about it
+        let mut fs = MockFileSystem::new("testdata/assets");
+        fs.supports_ranges = false;
+        let (status, headers, body) = probe(fs, Options::default());
+        assert_eq!(status, Status::Ok);
+        assert_eq!(headers.0.as_deref(), Some("none"));
+        assert_eq!(headers.1, None);
+        assert_eq!(body, "Hello World!");
+
+        let options = OptionsBuilder::new()
+            .no_range_pattern(Regex::new("^/hello").unwrap())
+            .into();
+        let (status, headers, body) = probe(MockFileSystem::new("testdata/assets"), options);
+        assert_eq!(status, Status::Ok);
+        assert_eq!(headers.0.as_deref(), Some("none"));
+        assert_eq!(headers.1, None);
+        assert_eq!(body, "Hello World!");
+    }
+
+    #[test]
+    fn test_head_never_opens() {
+        let head = |options: Options| {
+            let fs = MockFileSystem::new("testdata/site");
+            let opens = fs.opens.clone();
+            let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+            let client = Client::tracked(rocket).expect("valid rocket");
+
+            let resp = client.head("/about.html").dispatch();
+            assert_eq!(resp.status(), Status::Ok);
+            assert_eq!(resp.headers().get_one("Content-Type"), Some("text/html"));
+            let last_modified = resp.headers().get_one("Last-Modified").unwrap().to_string();
+            let resp = client
+                .head("/about.html")
+                .header(Header::new("If-Modified-Since", last_modified))
+                .dispatch();
+            assert_eq!(resp.status(), Status::NotModified);
+            assert_eq!(opens.load(Ordering::SeqCst), 0);
+
+            client.get("/about.html").dispatch();
+            assert_eq!(opens.load(Ordering::SeqCst), 1);
+        };
+
+        head(Options::default());
+        head(OptionsBuilder::new().no_range_pattern(Regex::new(".").unwrap()).into());
+        head(OptionsBuilder::new().precompressed_variants(true).into());
+        // Not even for transformed files, whose length is only known once they're read
+        head(OptionsBuilder::new().transform(|_, bytes| bytes).into());
+        head(OptionsBuilder::new().html_banner(Some("<p>Down soon</p>".to_string())).into());
+
+        // Without taking an open permit, and without a length the body would have
+        let options = OptionsBuilder::new().transform(|_, bytes| bytes).max_concurrent_opens(0);
+        let fs = MockFileSystem::new("testdata/site");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options.into()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let resp = client.head("/about.html").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.headers().get_one("Content-Length").is_none());
+        assert!(resp.headers().get_one("ETag").unwrap().starts_with("W/"));
+    }
+
+    #[test]
+    fn test_open_ended_range() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("Range", "bytes=5-"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PartialContent);
+        assert_eq!(resp.headers().get_one("Content-Range"), Some("bytes 5-11/12"));
+        assert_eq!(resp.headers().get_one("Content-Length"), Some("7"));
+        assert_eq!(resp.into_string(), Some(" World!".to_string()));
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("Range", "bytes=11-"))
+            .dispatch();
+        assert_eq!(resp.headers().get_one("Content-Range"), Some("bytes 11-11/12"));
+        assert_eq!(resp.headers().get_one("Content-Length"), Some("1"));
+        assert_eq!(resp.into_string(), Some("!".to_string()));
+    }
+
+    #[test]
+    fn test_filename_language() {
+        assert_eq!(filename_language("page.fr.html"), Some("fr"));
+        assert_eq!(filename_language("docs/index.pt-BR.html"), Some("pt-BR"));
+        assert_eq!(filename_language("index.es-419.html"), Some("es-419"));
+        assert_eq!(filename_language("page.html"), None);
+        assert_eq!(filename_language("fr.html"), None);
+        assert_eq!(filename_language("jquery.min.js"), None);
+        assert_eq!(filename_language("app.2x.png"), None);
+        assert_eq!(filename_language("fr.de/page.html"), None);
+    }
+
+    #[test]
+    fn test_content_language_from_filename() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/content-language");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("page.fr.html"), "Bonjour").unwrap();
+        std::fs::write(dir.join("page.html"), "Hello").unwrap();
+
+        let options = OptionsBuilder::new().content_language_from_filename(true).into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/page.fr.html").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Language"), Some("fr"));
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/html"));
+
+        let resp = client.get("/page.html").dispatch();
+        assert!(resp.headers().get_one("Content-Language").is_none());
+
+        // Off by default
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let resp = client.get("/page.fr.html").dispatch();
+        assert!(resp.headers().get_one("Content-Language").is_none());
+    }
+
+    #[test]
+    fn test_accepted_languages() {
+        assert_eq!(accepted_languages("fr"), vec!["fr"]);
+        assert_eq!(accepted_languages("de;q=0.5, fr-CH, *;q=0.1"), vec!["fr-CH", "fr", "de"]);
+        assert_eq!(accepted_languages("en;q=0, fr-FR, fr"), vec!["fr-FR", "fr"]);
+        assert!(accepted_languages("").is_empty());
+    }
+
+    #[test]
+    fn test_language_negotiation() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/language-negotiation");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("page.html"), "Hello").unwrap();
+        std::fs::write(dir.join("page.fr.html"), "Bonjour").unwrap();
+
+        let options = OptionsBuilder::new()
+            .language_negotiation(true)
+            .try_extensions(vec!["html".to_string()])
+            .into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let get = |accept_language: Option<&'static str>| {
+            let mut req = client.get("/page");
+            if let Some(accept_language) = accept_language {
+                req = req.header(Header::new("Accept-Language", accept_language));
+            }
+            let resp = req.dispatch();
+            assert_eq!(resp.status(), Status::Ok);
+            assert_eq!(resp.headers().get_one("Vary"), Some("Accept-Language"));
+            let language = resp.headers().get_one("Content-Language").map(str::to_string);
+            (language, resp.into_string().unwrap())
+        };
+
+        assert_eq!(get(Some("fr")), (Some("fr".to_string()), "Bonjour".to_string()));
+        assert_eq!(get(Some("de, fr;q=0.5")), (Some("fr".to_string()), "Bonjour".to_string()));
+        assert_eq!(get(Some("fr-CH")), (Some("fr".to_string()), "Bonjour".to_string()));
+        assert_eq!(get(Some("de")), (None, "Hello".to_string()));
+        assert_eq!(get(Some("../page")), (None, "Hello".to_string()));
+        assert_eq!(get(None), (None, "Hello".to_string()));
+
+        // Variants are still served directly
+        let resp = client
+            .get("/page.fr.html")
+            .header(Header::new("Accept-Language", "de"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("Bonjour".to_string()));
+    }
+
+    #[test]
+    fn test_plus_in_path() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/plus-in-path");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a+b.txt"), "plus").unwrap();
+        std::fs::write(dir.join("a b.txt"), "space").unwrap();
+
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/a+b.txt").dispatch();
+        assert_eq!(resp.into_string(), Some("plus".to_string()));
+        let resp = client.get("/a%2Bb.txt").dispatch();
+        assert_eq!(resp.into_string(), Some("plus".to_string()));
+        let resp = client.get("/a%20b.txt").dispatch();
+        assert_eq!(resp.into_string(), Some("space".to_string()));
+
+        let options = OptionsBuilder::new().plus_as_space(true).into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/a+b.txt").dispatch();
+        assert_eq!(resp.into_string(), Some("space".to_string()));
+        let resp = client.get("/a%2Bb.txt").dispatch();
+        assert_eq!(resp.into_string(), Some("plus".to_string()));
+    }
+
+    #[test]
+    fn test_hide_index_in_url() {
+        let options = OptionsBuilder::new()
+            .directory_listing_default_index("index.html")
+            .hide_index_in_url(true)
+            .into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new("testdata/site"), options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/docs/index.html").dispatch();
+        assert_eq!(resp.status(), Status::MovedPermanently);
+        assert_eq!(resp.headers().get_one("Location"), Some("/docs/"));
+
+        let resp = client.get("/docs/index.html?page=2").dispatch();
+        assert_eq!(resp.headers().get_one("Location"), Some("/docs/?page=2"));
+
+        let resp = client.get("/docs/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+
+        // Without the option the index is served under its own name as well
+        let options = OptionsBuilder::new()
+            .directory_listing_default_index("index.html")
+            .into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new("testdata/site"), options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/docs/index.html").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(
+            resp.into_bytes(),
+            Some(std::fs::read("testdata/site/docs/index.html").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_directory_listing_of_embedded_root() {
+        let bytes = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/target/test.package"));
+        let fs = EmbeddedFileSystem::from_bytes(bytes).expect("valid package");
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.into_string().unwrap();
+        assert!(body.contains(r#"href="hello.txt""#));
+        assert_eq!(body.matches(r#"href="inner/""#).count(), 1);
+        assert!(!body.contains("other.txt"));
+
+        let resp = client.get("/inner/").dispatch();
+        let body = resp.into_string().unwrap();
+        assert!(body.contains(r#"href="other.txt""#));
+        assert!(body.contains(r#"href="deeper/""#));
+    }
+
+    #[test]
+    fn test_directory_listing_filter() {
+        let fs = LocalFileSystem::new("testdata");
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/assets/?filter=zz").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.into_string().unwrap();
+        assert!(body.contains(r#"href="zzz.txt""#));
+        assert!(!body.contains(r#"href="hello.txt""#));
+        assert!(!body.contains(r#"href="inner/""#));
+        assert!(body.contains(r#"value="zz""#));
+
+        for uri in &["/assets/", "/assets/?filter="] {
+            let resp = client.get(*uri).dispatch();
+            assert_eq!(resp.status(), Status::Ok);
+            let body = resp.into_string().unwrap();
+            assert!(body.contains(r#"href="hello.txt""#));
+            assert!(body.contains(r#"href="inner/""#));
+            assert!(body.contains(r#"href="yyy.txt""#));
+            assert!(body.contains(r#"href="zzz.txt""#));
+        }
+    }
+
+    #[test]
+    fn test_directory_listing_hides_index_files() {
+        let fs = LocalFileSystem::new("testdata/site");
+        let options = OptionsBuilder::new()
+            .allow_directory_listing(true)
+            .hide_index_files(vec!["index.html".to_string()])
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/docs/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.into_string().unwrap();
+        assert!(body.contains(r#"href="guide.html""#));
+        assert!(!body.contains(r#"href="index.html""#));
+    }
+
+    #[test]
+    fn test_directory_listing_encodes_links() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/listing-links");
+        std::fs::create_dir_all(dir.join("sub dir#1")).unwrap();
+        for name in ["50%.txt", "a#b.txt", "why?.txt", "with space.txt"] {
+            std::fs::write(dir.join(name), name).unwrap();
+            std::fs::write(dir.join("sub dir#1").join(name), name).unwrap();
+        }
+
+        for disable_redirect in [false, true] {
+            let options = OptionsBuilder::new()
+                .allow_directory_listing(true)
+                .disable_directory_redirect(disable_redirect)
+                .into();
+            let rocket = rocket::build()
+                .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+            let client = Client::tracked(rocket).expect("valid rocket");
+
+            if !disable_redirect {
+                let resp = client.get("/sub%20dir%231").dispatch();
+                assert_eq!(resp.headers().get_one("Location"), Some("/sub%20dir%231/"));
+            }
+
+            let body = client.get("/").dispatch().into_string().unwrap();
+            let base = if disable_redirect { "/" } else { "" };
+            assert!(body.contains(&format!(r#"href="{}sub%20dir%231/">sub dir#1/<"#, base)));
+            let body = client.get("/sub%20dir%231/").dispatch().into_string().unwrap();
+            let base = if disable_redirect { "/sub%20dir%231/" } else { "" };
+            for (name, href) in [
+                ("50%.txt", "50%25.txt"),
+                ("a#b.txt", "a%23b.txt"),
+                ("why?.txt", "why%3F.txt"),
+                ("with space.txt", "with%20space.txt"),
+            ] {
+                // The name is shown as it is, the link leads to the file
+                assert!(body.contains(&format!(r#"href="{}{}">{}<"#, base, href, name)));
+                let resp = client.get(format!("/sub%20dir%231/{}", href)).dispatch();
+                assert_eq!(resp.into_string(), Some(name.to_string()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_hidden_index_files_with_default_index() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/hidden-index-files");
+        std::fs::create_dir_all(dir.join("served")).unwrap();
+        std::fs::create_dir_all(dir.join("listed")).unwrap();
+        std::fs::write(dir.join("served/index.html"), "index").unwrap();
+        std::fs::write(dir.join("served/index.htm"), "old index").unwrap();
+        std::fs::write(dir.join("listed/index.htm"), "old index").unwrap();
+        std::fs::write(dir.join("listed/page.html"), "page").unwrap();
+
+        let options = OptionsBuilder::new()
+            .allow_directory_listing(true)
+            .directory_listing_default_index("index.html")
+            .hide_index_files(vec!["index.htm".to_string()])
+            .into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        // The default index is served rather than listed, whatever is hidden
+        let resp = client.get("/served/").dispatch();
+        assert_eq!(resp.into_string(), Some("index".to_string()));
+
+        // Without it, the directory is listed without the hidden index files
+        let resp = client.get("/listed/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.into_string().unwrap();
+        assert!(body.contains(r#"href="page.html""#));
+        assert!(!body.contains(r#"href="index.htm""#));
+
+        // They can still be requested themselves
+        let resp = client.get("/listed/index.htm").dispatch();
+        assert_eq!(resp.into_string(), Some("old index".to_string()));
+    }
+
+    #[test]
+    fn test_directory_listing_without_redirect() {
+        let fs = LocalFileSystem::new("testdata");
+        let options = OptionsBuilder::new()
+            .prefix("/files")
+            .allow_directory_listing(true)
+            .disable_directory_redirect(true)
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        for uri in &["/files/assets", "/files/assets/"] {
+            let resp = client.get(*uri).dispatch();
+            assert_eq!(resp.status(), Status::Ok);
+            let body = resp.into_string().unwrap();
+            assert!(body.contains(r#"href="/files/assets/hello.txt""#));
+            assert!(body.contains(r#"href="/files/assets/inner/""#));
+        }
+    }
+
+    #[test]
+    fn test_prefix_root_without_trailing_slash() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let options = OptionsBuilder::new()
+            .prefix("/assets")
+            .allow_directory_listing(true)
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/assets").dispatch();
+        assert_eq!(resp.status(), Status::Found);
+        assert_eq!(resp.headers().get_one("Location"), Some("/assets/"));
+
+        let resp = client.get("/assets/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.into_string().unwrap().contains(r#"href="hello.txt""#));
+
+        let fs = LocalFileSystem::new("testdata/site/docs");
+        let options = OptionsBuilder::new()
+            .prefix("/docs")
+            .directory_listing_default_index("index.html")
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/docs").dispatch();
+        assert_eq!(resp.status(), Status::Found);
+        assert_eq!(resp.headers().get_one("Location"), Some("/docs/"));
+
+        let resp = client.get("/docs/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(
+            resp.into_bytes(),
+            Some(std::fs::read("testdata/site/docs/index.html").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_directory_listing_ignores_range() {
+        let fs = LocalFileSystem::new("testdata");
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client
+            .get("/assets/")
+            .header(Header::new("Range", "bytes=0-10"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Accept-Ranges"), Some("none"));
+        assert!(resp.headers().get_one("Content-Range").is_none());
+        let body = resp.into_string().unwrap();
+        assert!(body.contains(r#"href="hello.txt""#));
+        assert!(body.ends_with("</html>"));
+    }
+
+    #[test]
+    fn test_max_listing_entries() {
+        let fs = LocalFileSystem::new("testdata");
+        let options = OptionsBuilder::new()
+            .allow_directory_listing(true)
+            .max_listing_entries(2)
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let body = client.get("/assets/").dispatch().into_string().unwrap();
+        assert_eq!(body.matches(r#"<td class="name">"#).count(), 2);
+        assert!(body.contains(r#"<p class="truncated">"#));
+
+        let body = client.get("/assets/inner/").dispatch().into_string().unwrap();
+        assert_eq!(body.matches(r#"<td class="name">"#).count(), 2);
+        assert!(!body.contains(r#"<p class="truncated">"#));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_directory_listing_skips_unreadable_entries() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/unreadable-listing");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("readable.txt"), "readable").unwrap();
+        std::fs::write(dir.join(OsStr::from_bytes(b"invalid-\xff.txt")), "invalid").unwrap();
+
+        let fs = LocalFileSystem::new(&dir);
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.into_string().unwrap();
+        assert!(body.contains(r#"href="readable.txt""#));
+        assert!(!body.contains("invalid-"));
+    }
+
+    #[test]
+    fn test_empty_directory_listing() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/empty-listing");
+        std::fs::create_dir_all(dir.join("empty")).unwrap();
+
+        let fs = LocalFileSystem::new(&dir);
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/empty/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.into_string().unwrap().contains(r#"<p class="empty">"#));
+
+        let resp = client.get("/").dispatch();
+        assert!(!resp.into_string().unwrap().contains(r#"<p class="empty">"#));
+
+        let fs = LocalFileSystem::new(&dir);
+        let options = OptionsBuilder::new()
+            .allow_directory_listing(true)
+            .empty_directory_not_found(true)
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/empty/").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+        let resp = client.get("/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_directory_of_hidden_entries_is_empty() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/hidden-only-listing");
+        std::fs::create_dir_all(dir.join("hidden/.git")).unwrap();
+        std::fs::write(dir.join("hidden/.gitkeep"), "").unwrap();
+        std::fs::write(dir.join("hidden/index.htm"), "index").unwrap();
+        let client = |options: OptionsBuilder| {
+            let options = options
+                .allow_directory_listing(true)
+                .hide_index_files(vec!["index.htm".to_string()])
+                .into();
+            let rocket = rocket::build()
+                .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+            Client::tracked(rocket).expect("valid rocket")
+        };
+
+        let client_unlisted = client(OptionsBuilder::new().list_hidden_paths(false));
+        let resp = client_unlisted.get("/hidden/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.into_string().unwrap();
+        assert!(body.contains(r#"<p class="empty">"#));
+        assert!(!body.contains(".git"));
+
+        let options = OptionsBuilder::new()
+            .list_hidden_paths(false)
+            .empty_directory_not_found(true);
+        let client_not_found = client(options);
+        let resp = client_not_found.get("/hidden/").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+
+        // As long as hidden entries are listed, there's something to show
+        let options = OptionsBuilder::new().empty_directory_not_found(true);
+        let client_hidden = client(options);
+        let resp = client_hidden.get("/hidden/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.into_string().unwrap();
+        assert!(!body.contains(r#"<p class="empty">"#));
+        assert!(body.contains(r#"href=".gitkeep""#));
+    }
+
+    #[test]
+    fn test_listing_dirs() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/listing-dirs");
+        for sub in &["downloads/old", "private", "downloads-extra"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        std::fs::write(dir.join("downloads/app.zip"), "zip").unwrap();
+        std::fs::write(dir.join("downloads/old/app-1.zip"), "zip").unwrap();
+        std::fs::write(dir.join("private/secret.txt"), "secret").unwrap();
+        std::fs::write(dir.join("downloads-extra/other.txt"), "other").unwrap();
+
+        let options = OptionsBuilder::new()
+            .listing_dirs(vec!["/downloads/".to_string()])
+            .into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/downloads/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.into_string().unwrap().contains(r#"href="app.zip""#));
+        let resp = client.get("/downloads/old/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+
+        for path in &["/private/", "/downloads-extra/", "/"] {
+            assert_eq!(client.get(*path).dispatch().status(), Status::NotFound, "{}", path);
+        }
+        assert_eq!(client.get("/private/secret.txt").dispatch().status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_hidden_paths() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/hidden-paths");
+        std::fs::create_dir_all(dir.join(".well-known")).unwrap();
+        std::fs::write(dir.join(".well-known/security.txt"), "Contact: a@b.c").unwrap();
+        std::fs::write(dir.join(".env"), "SECRET=1").unwrap();
+        std::fs::write(dir.join("visible.txt"), "visible").unwrap();
+        let client = |options: OptionsBuilder| {
+            let options = options.allow_directory_listing(true).into();
+            let rocket = rocket::build()
+                .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+            Client::tracked(rocket).expect("valid rocket")
+        };
+
+        let default = client(OptionsBuilder::new());
+        let body = default.get("/").dispatch().into_string().unwrap();
+        assert!(body.contains(r#"href=".well-known/""#));
+        assert!(body.contains(r#"href=".env""#));
+
+        let unlisted = client(OptionsBuilder::new().list_hidden_paths(false));
+        let resp = unlisted.get("/.well-known/security.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(unlisted.get("/.env").dispatch().status(), Status::Ok);
+        let body = unlisted.get("/").dispatch().into_string().unwrap();
+        assert!(body.contains(r#"href="visible.txt""#));
+        assert!(!body.contains(".well-known"));
+        assert!(!body.contains(".env"));
+        assert_eq!(unlisted.get("/.well-known/").dispatch().status(), Status::NotFound);
+
+        let unserved = client(OptionsBuilder::new().serve_hidden_paths(false));
+        let resp = unserved.get("/.well-known/security.txt").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+        assert_eq!(unserved.get("/.env").dispatch().status(), Status::NotFound);
+        assert_eq!(unserved.get("/visible.txt").dispatch().status(), Status::Ok);
+        let body = unserved.get("/").dispatch().into_string().unwrap();
+        assert!(!body.contains(".env"));
+    }
+
+    #[test]
+    fn test_normalize_backslashes() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/backslash-paths");
+        std::fs::create_dir_all(dir.join("test")).unwrap();
+        std::fs::write(dir.join("test/lib.rs"), "fn main() {}").unwrap();
+        let mut package = Vec::new();
+        create_package_from_dir(&dir, &mut package).expect("unable to create package");
+
+        let check = |client: Client| {
+            let resp = client.get("/test/lib.rs").dispatch();
+            assert_eq!(resp.into_string().unwrap(), "fn main() {}");
+            // A raw `\` isn't valid in a URI, Rocket turns those away before we see them
+            let resp = client.get("/test%5Clib.rs").dispatch();
+            assert_eq!(resp.status(), Status::Ok);
+            assert_eq!(resp.into_string().unwrap(), "fn main() {}");
+            let resp = client.get("/test%5C..%5C..%5CCargo.toml").dispatch();
+            assert_eq!(resp.status(), Status::Forbidden);
+        };
+        let options = || OptionsBuilder::new().normalize_backslashes(true).into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options()).unwrap());
+        check(Client::tracked(rocket).expect("valid rocket"));
+        let fs = EmbeddedFileSystem::from_vec(package).expect("valid package");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options()).unwrap());
+        check(Client::tracked(rocket).expect("valid rocket"));
+
+        // Without the option a backslash is part of the file name
+        let fs = LocalFileSystem::new(&dir);
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        assert_eq!(client.get("/test%5Clib.rs").dispatch().status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_encoded_traversal() {
+        // `testdata/../Cargo.toml` exists, so anything but a 403 means we got out
+        let client = |options: OptionsBuilder| {
+            let fs = LocalFileSystem::new("testdata");
+            let rocket =
+                rocket::build().attach(StaticFileServer::new(fs, options.into()).unwrap());
+            Client::tracked(rocket).expect("valid rocket")
+        };
+        let paths = [
+            "/%2e%2e/Cargo.toml",
+            "/%2E%2E/Cargo.toml",
+            "/..%2fCargo.toml",
+            "/assets/..%2f..%2fCargo.toml",
+            "/assets%5C..%5C..%5CCargo.toml",
+            "/..%5CCargo.toml",
+        ];
+        for options in [OptionsBuilder::new(), OptionsBuilder::new().normalize_backslashes(true)] {
+            let client = client(options);
+            for path in paths {
+                assert_eq!(client.get(path).dispatch().status(), Status::Forbidden, "{}", path);
+                assert_eq!(client.head(path).dispatch().status(), Status::Forbidden, "{}", path);
+            }
+            assert_eq!(client.get("/assets/hello.txt").dispatch().status(), Status::Ok);
+        }
+    }
+
+    #[test]
+    fn test_local_path_valid() {
+        let fs = LocalFileSystem::new("testdata");
+        let rt = rocket::tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            assert!(fs.path_valid("assets/hello.txt").await);
+            assert!(!fs.path_valid("../Cargo.toml").await);
+            assert!(!fs.path_valid("assets/../../Cargo.toml").await);
+            assert!(!fs.path_valid("/etc/passwd").await);
+        });
+    }
+
+    #[test]
+    fn test_hidden_paths_archive() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/hidden-archive");
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::create_dir_all(dir.join("public")).unwrap();
+        std::fs::write(dir.join(".git/config"), "[core]").unwrap();
+        std::fs::write(dir.join(".env"), "SECRET=1").unwrap();
+        std::fs::write(dir.join("public/.htaccess"), "Deny from all").unwrap();
+        std::fs::write(dir.join("public/visible.txt"), "visible").unwrap();
+        let names = |options: OptionsBuilder| {
+            let options = options.archive_download(true).into();
+            let rocket = rocket::build()
+                .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+            let client = Client::tracked(rocket).expect("valid rocket");
+            let bytes = client.get("/?download=zip").dispatch().into_bytes().unwrap();
+            let zip = zip::ZipArchive::new(std::io::Cursor::new(bytes)).expect("valid zip");
+            let mut names: Vec<String> = zip.file_names().map(str::to_string).collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            names(OptionsBuilder::new()),
+            vec![".env", ".git/config", "public/.htaccess", "public/visible.txt"]
+        );
+        let visible = names(OptionsBuilder::new().serve_hidden_paths(false));
+        assert_eq!(visible, vec!["public/visible.txt"]);
+        let visible = names(OptionsBuilder::new().list_hidden_paths(false));
+        assert_eq!(visible, vec!["public/visible.txt"]);
+    }
+
+    #[test]
+    fn test_checksum_query() {
+        let client = |options: OptionsBuilder| {
+            let fs = LocalFileSystem::new("testdata/assets");
+            let rocket =
+                rocket::build().attach(StaticFileServer::new(fs, options.into()).unwrap());
+            Client::tracked(rocket).expect("valid rocket")
+        };
+
+        let client_checksums = client(OptionsBuilder::new().allow_checksum_query(true));
+        let resp = client_checksums.get("/hello.txt?checksum=sha256").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/plain; charset=utf-8"));
+        assert_eq!(
+            resp.into_string().unwrap(),
+            "7f83b1657ff1fc53b92dc18148a1d65dfc2d4b1fa3d677284addd200126d9069"
+        );
+        let resp = client_checksums.get("/hello.txt?checksum=crc32").dispatch();
+        assert_eq!(resp.into_string().unwrap(), "1c291ca3");
+        let resp = client_checksums.get("/hello.txt?checksum=md5").dispatch();
+        assert_eq!(resp.status(), Status::BadRequest);
+        let resp = client_checksums.get("/missing.txt?checksum=sha256").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+
+        // Without the query the file itself is served
+        let resp = client_checksums.get("/hello.txt").dispatch();
+        assert_eq!(resp.into_string().unwrap(), "Hello World!");
+
+        // And without the option the query is ignored
+        let client_default = client(OptionsBuilder::new());
+        let resp = client_default.get("/hello.txt?checksum=sha256").dispatch();
+        assert_eq!(resp.into_string().unwrap(), "Hello World!");
+    }
+
+    #[test]
+    fn test_listing_disabled_status() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        assert_eq!(client.get("/inner/").dispatch().status(), Status::NotFound);
+
+        let fs = LocalFileSystem::new("testdata/assets");
+        let options = OptionsBuilder::new()
+            .listing_disabled_status(Status::Forbidden)
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        for path in ["/inner/", "/"] {
+            let resp = client.get(path).dispatch();
+            assert_eq!(resp.status(), Status::Forbidden);
+            assert!(resp.headers().get_one("Content-Type").is_none());
+            assert_eq!(resp.into_string(), Some(String::new()));
+        }
+        assert_eq!(client.get("/missing/").dispatch().status(), Status::NotFound);
+        assert_eq!(client.get("/missing.txt").dispatch().status(), Status::NotFound);
+        assert_eq!(client.get("/hello.txt").dispatch().status(), Status::Ok);
+
+        // Only applies while listing is disabled
+        let fs = LocalFileSystem::new("testdata/assets");
+        let options = OptionsBuilder::new()
+            .listing_disabled_status(Status::Forbidden)
+            .allow_directory_listing(true)
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        assert_eq!(client.get("/inner/").dispatch().status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_listing_feed() {
+        use rocket::http::uri::Host;
+
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/listing-feed");
+        std::fs::create_dir_all(dir.join("releases/old")).unwrap();
+        let release = |name: &str, secs: u64| {
+            let file = std::fs::File::create(dir.join("releases").join(name)).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)).unwrap();
+        };
+        release("v1 & v1.1.tar.gz", 1_600_000_000);
+        release("v2.tar.gz", 1_700_000_000);
+        let client = |options: OptionsBuilder| {
+            let options = options.allow_directory_listing(true).into();
+            let rocket = rocket::build()
+                .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+            Client::tracked(rocket).expect("valid rocket")
+        };
+
+        let client_feed = client(OptionsBuilder::new().listing_feed(true));
+        let mut req = client_feed.get("/releases/?format=rss");
+        req.inner_mut().set_host(Host::parse("example.com").unwrap());
+        let resp = req.dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(
+            resp.headers().get_one("Content-Type"),
+            Some("application/rss+xml; charset=utf-8")
+        );
+        let feed = resp.into_string().unwrap();
+
+        // Every tag is closed again in the order it was opened, and text has no bare `<` or `&`
+        let tags = Regex::new(r"<(/?)([a-zA-Z]+)[^<>]*?(/?)>").unwrap();
+        let mut open = Vec::new();
+        for tag in tags.captures_iter(&feed) {
+            match (&tag[1], &tag[3]) {
+                ("/", _) => assert_eq!(open.pop(), Some(tag[2].to_string())),
+                (_, "/") => {}
+                _ => open.push(tag[2].to_string()),
+            }
+        }
+        assert!(open.is_empty());
+        let body = feed.trim_start_matches(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+        let text = tags.replace_all(body, "");
+        assert!(!text.contains('<'));
+        assert!(!Regex::new(r"&[^#a-z]").unwrap().is_match(&text));
+
+        // The files are there newest first, the directory is not
+        let items: Vec<&str> = feed.split("<item>").skip(1).collect();
+        assert_eq!(items.len(), 2);
+        assert!(items[0].contains("<link>http://example.com/releases/v2.tar.gz</link>"));
+        assert!(items[0].contains("<pubDate>Tue, 14 Nov 2023 22:13:20 GMT</pubDate>"));
+        assert!(items[1].contains("<title>v1 &amp; v1.1.tar.gz</title>"));
+        assert!(items[1].contains("<pubDate>Sun, 13 Sep 2020 12:26:40 GMT</pubDate>"));
+        assert!(!feed.contains("old"));
+
+        // Names are percent-encoded in links, in the directory's as well
+        let link = "http://example.com/releases/v1%20%26%20v1.1.tar.gz";
+        assert!(items[1].contains(&format!("<link>{}</link>", link)));
+        assert!(items[1].contains(&format!("<guid>{}</guid>", link)));
+        std::fs::create_dir_all(dir.join("pre releases")).unwrap();
+        std::fs::File::create(dir.join("pre releases/v3 beta.tar.gz")).unwrap();
+        let mut req = client_feed.get("/pre%20releases/?format=rss");
+        req.inner_mut().set_host(Host::parse("example.com").unwrap());
+        let feed = req.dispatch().into_string().unwrap();
+        assert!(feed.contains("<link>http://example.com/pre%20releases/</link>"));
+        assert!(feed.contains("<link>http://example.com/pre%20releases/v3%20beta.tar.gz</link>"));
+
+        // Without a host the links are absolute paths
+        let feed = client_feed.get("/releases/?format=rss").dispatch().into_string().unwrap();
+        assert!(feed.contains("<link>/releases/v2.tar.gz</link>"));
+
+        // The canonical host is linked to instead of the one the client used
+        let client_canonical = client(
+            OptionsBuilder::new()
+                .listing_feed(true)
+                .canonical_host(Some("static.example.com".to_string())),
+        );
+        let feed = client_canonical
+            .get("/releases/?format=rss")
+            .dispatch()
+            .into_string()
+            .unwrap();
+        assert!(feed.contains("<link>http://static.example.com/releases/v2.tar.gz</link>"));
+        let mut req = client_canonical.get("/releases/?format=rss");
+        req.inner_mut().set_host(Host::parse("STATIC.example.com").unwrap());
+        let feed = req.dispatch().into_string().unwrap();
+        assert!(feed.contains("<link>http://static.example.com/releases/v2.tar.gz</link>"));
+
+        // Without the option it's the usual listing
+        let client_default = client(OptionsBuilder::new());
+        let resp = client_default.get("/releases/?format=rss").dispatch();
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/html; charset=utf-8"));
+    }
+
+    #[test]
+    fn test_non_html_directory_index() {
+        let fs = LocalFileSystem::new("testdata/site");
+        let options = OptionsBuilder::new()
+            .directory_listing_default_index("README.md")
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/readme/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/markdown"));
+        assert_eq!(
+            resp.into_bytes(),
+            Some(std::fs::read("testdata/site/readme/README.md").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_archive_download() {
+        let fs = LocalFileSystem::new("testdata");
+        let options = OptionsBuilder::new().archive_download(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/assets/?download=zip").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("application/zip"));
+        assert_eq!(
+            resp.headers().get_one("Content-Disposition"),
+            Some(r#"attachment; filename="assets.zip""#)
+        );
+
+        let bytes = resp.into_bytes().unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(bytes)).expect("valid zip");
+        let names: Vec<&str> = zip.file_names().collect();
+        assert_eq!(names.len(), 5);
+        for name in &[
+            "hello.txt",
+            "inner/deeper/deep.txt",
+            "inner/other.txt",
+            "yyy.txt",
+            "zzz.txt",
+        ] {
+            assert!(names.contains(name));
+        }
+
+        let mut hello = String::new();
+        std::io::Read::read_to_string(&mut zip.by_name("hello.txt").unwrap(), &mut hello)
+            .unwrap();
+        assert_eq!(hello, "Hello World!");
+
+        let resp = client.get("/assets/inner?download=tar").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("application/x-tar"));
+        let bytes = resp.into_bytes().unwrap();
+        assert_eq!(bytes.len() % 512, 0);
+        assert!(bytes.starts_with(b"deeper/deep.txt\0"));
+
+        let resp = client.get("/assets/?download=rar").dispatch();
+        assert_eq!(resp.status(), Status::BadRequest);
+
+        // Without the option, the query doesn't matter
+        let fs = LocalFileSystem::new("testdata");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let resp = client.get("/assets/?download=zip").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_archive_opens_lazily() {
+        let fs = MockFileSystem::new("testdata");
+        let (opens, open_files) = (fs.opens.clone(), fs.open_files.clone());
+        let options = OptionsBuilder::new().archive_download(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        // Nothing is opened before the body is read, and every file is closed after it
+        let resp = client.get("/assets/?download=zip").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(opens.load(Ordering::SeqCst), 0);
+        let bytes = resp.into_bytes().unwrap();
+        assert!(zip::ZipArchive::new(std::io::Cursor::new(bytes)).is_ok());
+        assert_eq!(opens.load(Ordering::SeqCst), 5);
+        assert_eq!(open_files.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_archive_limits() {
+        let entry = |path: String, size: u64| ArchiveEntry {
+            path,
+            size,
+            modified: SystemTime::UNIX_EPOCH,
+            open: Box::pin(async { Ok(Box::new(Cursor::new(Vec::new())) as Box<_>) }),
+        };
+
+        let many = (0..=u16::MAX as usize).map(|i| entry(i.to_string(), 0)).collect();
+        let err = ArchiveStream::new(ArchiveFormat::Zip, many).err().unwrap();
+        assert_eq!(err.to_string(), "too many files for a zip archive");
+        let many = (0..=u16::MAX as usize).map(|i| entry(i.to_string(), 0)).collect();
+        assert!(ArchiveStream::new(ArchiveFormat::Tar, many).is_ok());
+
+        let large = vec![entry("a".into(), 3 << 30), entry("b".into(), 2 << 30)];
+        let err = ArchiveStream::new(ArchiveFormat::Zip, large).err().unwrap();
+        assert_eq!(err.to_string(), "too large for a zip archive");
+        let too_large = vec![entry("a".into(), 5 << 30)];
+        let err = ArchiveStream::new(ArchiveFormat::Tar, too_large).err().unwrap();
+        assert_eq!(err.to_string(), "a is too large to archive");
+    }
+
+    #[test]
+    fn test_tar_entries_keep_their_size() {
+        let entry = |path: &str, size: u64, content: &'static str| ArchiveEntry {
+            path: path.to_string(),
+            size,
+            modified: SystemTime::UNIX_EPOCH,
+            open: Box::pin(async move { Ok(Box::new(Cursor::new(content)) as Box<_>) }),
+        };
+        let rt = rocket::tokio::runtime::Runtime::new().unwrap();
+        let read = |entries| {
+            let mut archive = ArchiveStream::new(ArchiveFormat::Tar, entries).unwrap();
+            let mut bytes = Vec::new();
+            rt.block_on(archive.read_to_end(&mut bytes)).map(|_| bytes)
+        };
+
+        // A file which grew since its size was taken is cut to the size in its header
+        let entries = vec![entry("grown.txt", 5, "Hello World!"), entry("b.txt", 2, "ok")];
+        let bytes = read(entries).expect("unable to read archive");
+        assert_eq!(bytes.len(), 4 * 512 + 1024);
+        assert_eq!(&bytes[512..517], b"Hello");
+        assert!(bytes[517..1024].iter().all(|b| *b == 0));
+        assert!(bytes[1024..].starts_with(b"b.txt\0"));
+        assert_eq!(&bytes[1536..1538], b"ok");
+
+        let err = read(vec![entry("shrunk.txt", 20, "Hello World!")]).err().unwrap();
+        assert_eq!(err.to_string(), "shrunk.txt changed while archiving");
+    }
+
+    #[test]
+    fn test_archive_download_name() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/archive-names");
+        for name in ["say \"hi\"", "größe"] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+            std::fs::write(dir.join(name).join("a.txt"), "a").unwrap();
+        }
+        let options = OptionsBuilder::new().archive_download(true).into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/say%20%22hi%22/?download=zip").dispatch();
+        assert_eq!(
+            resp.headers().get_one("Content-Disposition"),
+            Some(r#"attachment; filename="say \"hi\".zip""#)
+        );
+        let resp = client.get("/gr%C3%B6%C3%9Fe/?download=tar").dispatch();
+        assert_eq!(
+            resp.headers().get_one("Content-Disposition"),
+            Some("attachment; filename*=UTF-8''gr%C3%B6%C3%9Fe.tar")
+        );
+    }
+
+    #[test]
+    fn test_head_and_open_ended_range_agree() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.head("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let head_length = resp
+            .headers()
+            .get_one("Content-Length")
+            .expect("no content length")
+            .to_owned();
+        assert_eq!(head_length, "12");
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("Range", "bytes=0-"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PartialContent);
+        assert_eq!(resp.headers().get_one("Content-Length"), Some(head_length.as_str()));
+        assert_eq!(resp.into_bytes().unwrap().len(), 12);
+    }
+
+    #[test]
+    fn test_single_byte_ranges() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        for (range, content_range, body) in &[
+            ("bytes=0-0", "bytes 0-0/12", "H"),
+            ("bytes=11-11", "bytes 11-11/12", "!"),
+        ] {
+            let resp = client
+                .get("/hello.txt")
+                .header(Header::new("Range", *range))
+                .dispatch();
+            assert_eq!(resp.status(), Status::PartialContent);
+            assert_eq!(resp.headers().get_one("Content-Length"), Some("1"));
+            assert_eq!(resp.headers().get_one("Content-Range"), Some(*content_range));
+            assert_eq!(resp.into_string(), Some(body.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_range_end_past_file() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("Range", "bytes=0-1000000"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PartialContent);
+        assert_eq!(resp.headers().get_one("Content-Range"), Some("bytes 0-11/12"));
+        assert_eq!(resp.headers().get_one("Content-Length"), Some("12"));
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("Range", "bytes=6-20"))
+            .dispatch();
+        assert_eq!(resp.headers().get_one("Content-Range"), Some("bytes 6-11/12"));
+        assert_eq!(resp.headers().get_one("Content-Length"), Some("6"));
+        assert_eq!(resp.into_string(), Some("World!".to_string()));
+    }
+
+    #[test]
+    fn test_unsatisfiable_range() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        for range in &["bytes=12-", "bytes=12-20", "bytes=100-200", "bytes=-0"] {
+            let resp = client
+                .get("/hello.txt")
+                .header(Header::new("Range", *range))
+                .dispatch();
+            assert_eq!(resp.status(), Status::RangeNotSatisfiable, "{}", range);
+            assert_eq!(resp.headers().get_one("Content-Range"), Some("bytes */12"));
+            assert_eq!(resp.into_bytes(), Some(Vec::new()));
+        }
+
+        // Several ranges are still answered with the whole file, even if none is satisfiable
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("Range", "bytes=20-,30-"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+    }
+
+    #[test]
+    fn test_other_range_units_ignored() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        for range in &["items=0-4", "items=20-"] {
+            let resp = client
+                .get("/hello.txt")
+                .header(Header::new("Range", *range))
+                .dispatch();
+            assert_eq!(resp.status(), Status::Ok, "{}", range);
+            assert!(resp.headers().get_one("Content-Range").is_none());
+            assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_if_range_date() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        let last_modified = resp
+            .headers()
+            .get_one("Last-Modified")
+            .expect("no last modified")
+            .to_owned();
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("Range", "bytes=6-10"))
+            .header(Header::new("If-Range", last_modified))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PartialContent);
+        assert_eq!(resp.into_string(), Some("World".to_string()));
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("Range", "bytes=6-10"))
+            .header(Header::new("If-Range", "Thu, 01 Jan 1970 00:00:00 GMT"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.headers().get_one("Content-Range").is_none());
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+    }
+
+    #[test]
+    fn test_stat() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let rt = rocket::tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let meta = std::fs::metadata("testdata/assets/hello.txt").unwrap();
+            assert_eq!(
+                fs.stat("hello.txt").await.unwrap(),
+                Some(FileMeta::File(12, meta.modified().unwrap()))
+            );
+            assert_eq!(fs.stat("inner").await.unwrap(), Some(FileMeta::Dir));
+            assert_eq!(fs.stat("not-there").await.unwrap(), None);
+
+            // The embedded filesystem relies on the default implementation
+            let bytes = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/target/test.package"));
+            let fs = EmbeddedFileSystem::from_bytes(bytes).unwrap();
+            match fs.stat("hello.txt").await.unwrap() {
+                Some(FileMeta::File(size, _)) => assert_eq!(size, 12),
+                _ => panic!("hello.txt is not a file"),
+            }
+            assert_eq!(fs.stat("inner").await.unwrap(), Some(FileMeta::Dir));
+            assert_eq!(fs.stat("not-there").await.unwrap(), None);
+        });
+
+        let fs = MockFileSystem::new("testdata/assets");
+        let stats = fs.stats.clone();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+        assert_eq!(stats.load(Ordering::SeqCst), 1);
+
+        // Gzipped only files and pretty URLs are looked up with a stat per candidate as well
+        let mut fs = MockFileSystem::new("testdata");
+        let roots = vec!["testdata/gzip".into(), "testdata/site".into()];
+        fs.inner = LocalFileSystem::with_roots(roots);
+        let (stats, is_file_calls) = (fs.stats.clone(), fs.is_file_calls.clone());
+        let options = OptionsBuilder::new()
+            .gzip_only_assets(true)
+            .try_extensions(vec!["htm".to_string(), "html".to_string()])
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client
+            .get("/app.js")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Encoding"), Some("gzip"));
+        assert_eq!(stats.swap(0, Ordering::SeqCst), 2);
+
+        let resp = client.get("/about").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/html"));
+        // Only with content_encoding may a gzipped `about.gz` be decoded for this client
+        let gzip_stats = if cfg!(feature = "content_encoding") { 1 } else { 0 };
+        assert_eq!(stats.swap(0, Ordering::SeqCst), 3 + gzip_stats);
+        assert_eq!(is_file_calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "content_encoding")]
+    fn test_head_with_content_encoding() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let options = OptionsBuilder::new().compress_min_size(0).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client
+            .head("/hello.txt")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Encoding"), Some("gzip"));
+        assert!(resp.headers().get_one("Content-Length").is_none());
+
+        let resp = client.head("/hello.txt").dispatch();
+        assert!(resp.headers().get_one("Content-Encoding").is_none());
+        assert_eq!(resp.headers().get_one("Content-Length"), Some("12"));
+    }
+
+    #[test]
+    fn test_embedded_range() {
+        let bytes = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/target/test.package"));
+        let fs = EmbeddedFileSystem::from_bytes(bytes).expect("valid package");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("Range", "bytes=6-10"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PartialContent);
+        assert_eq!(resp.body().preset_size(), Some(5));
+        assert_eq!(resp.into_string(), Some("World".to_string()));
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.body().preset_size(), Some(12));
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+    }
+
+    #[test]
+    fn test_if_modified_since_subsecond_mtime() {
+        let fs = MockFileSystem {
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_millis(10_300)),
+            ..MockFileSystem::new("testdata/assets")
+        };
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(
+            resp.headers().get_one("Last-Modified"),
+            Some("Thu, 01 Jan 1970 00:00:10 GMT")
+        );
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("If-Modified-Since", "Thu, 01 Jan 1970 00:00:10 GMT"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::NotModified);
+
+        // Less than a second apart, but a different second than the file's
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("If-Modified-Since", "Thu, 01 Jan 1970 00:00:11 GMT"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_client_dropping_mid_transfer() {
+        use std::io::Read;
+
+        let fs = MockFileSystem::new("src");
+        let open_files = fs.open_files.clone();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let mut resp = client.get("/lib.rs").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let mut start = [0u8; 16];
+        resp.read_exact(&mut start).unwrap();
+        assert_eq!(open_files.load(Ordering::SeqCst), 1);
+
+        // The client goes away before the body has been sent completely
+        drop(resp);
+        assert_eq!(open_files.load(Ordering::SeqCst), 0);
+
+        // And the server happily serves the next request
+        let resp = client.get("/lib.rs").dispatch();
+        assert_eq!(resp.into_bytes(), Some(std::fs::read("src/lib.rs").unwrap()));
+        assert_eq!(open_files.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_on_bytes_sent() {
+        use std::io::Read;
+        use std::sync::Mutex;
+
+        let sent: Arc<Mutex<Vec<(String, u64)>>> = Arc::new(Mutex::new(Vec::new()));
+        let reported = sent.clone();
+        let options = OptionsBuilder::new()
+            .on_bytes_sent(move |path, bytes| {
+                reported.lock().unwrap().push((path.to_string(), bytes));
+            })
+            .into();
+        let fs = LocalFileSystem::new("");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let last = || sent.lock().unwrap().pop().expect("bytes reported");
+
+        let resp = client.get("/testdata/assets/hello.txt").dispatch();
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+        assert_eq!(last(), ("/testdata/assets/hello.txt".to_string(), 12));
+
+        let resp = client
+            .get("/testdata/assets/hello.txt")
+            .header(Header::new("Range", "bytes=6-10"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("World".to_string()));
+        assert_eq!(last().1, 5);
+
+        let resp = client.head("/testdata/assets/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        drop(resp);
+        assert_eq!(last().1, 0);
+
+        // The client goes away after the first bytes
+        let size = std::fs::metadata("src/lib.rs").unwrap().len();
+        let mut resp = client.get("/src/lib.rs").dispatch();
+        let mut start = [0u8; 16];
+        resp.read_exact(&mut start).unwrap();
+        drop(resp);
+        let (_, bytes) = last();
+        assert!((16..size).contains(&bytes), "{} of {} bytes reported", bytes, size);
+        assert!(sent.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "content_encoding")]
+    fn test_on_bytes_sent_compressed() {
+        use std::sync::Mutex;
+
+        let sent = Arc::new(Mutex::new(0));
+        let reported = sent.clone();
+        let options = OptionsBuilder::new()
+            .on_bytes_sent(move |_, bytes| *reported.lock().unwrap() = bytes)
+            .into();
+        let fs = LocalFileSystem::new("src");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client
+            .get("/lib.rs")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(resp.headers().get_one("Content-Encoding"), Some("gzip"));
+        let body = resp.into_bytes().unwrap();
+        assert!((body.len() as u64) < std::fs::metadata("src/lib.rs").unwrap().len());
+        assert_eq!(*sent.lock().unwrap(), body.len() as u64);
+    }
+
+    #[test]
+    fn test_max_concurrent_opens() {
+        use std::io::Read;
+
+        let fs = MockFileSystem::new("src");
+        let open_files = fs.open_files.clone();
+        let options = OptionsBuilder::new().max_concurrent_opens(1).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        // The first body is still being sent, so its file stays open
+        let mut first = client.get("/lib.rs").dispatch();
+        assert_eq!(first.status(), Status::Ok);
+        let mut start = [0u8; 16];
+        first.read_exact(&mut start).unwrap();
+
+        let resp = client.get("/options.rs").dispatch();
+        assert_eq!(resp.status(), Status::ServiceUnavailable);
+        assert_eq!(open_files.load(Ordering::SeqCst), 1);
+
+        drop(first);
+        let resp = client.get("/options.rs").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.into_bytes(), Some(std::fs::read("src/options.rs").unwrap()));
+    }
+
+    #[test]
+    fn test_max_concurrent_opens_archive() {
+        use std::io::Read;
+
+        let fs = MockFileSystem::new("testdata");
+        let open_files = fs.open_files.clone();
+        let options = OptionsBuilder::new()
+            .max_concurrent_opens(1)
+            .archive_download(true)
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        // Past the first local header, the archive has its first file open and the permit
+        let mut archive = client.get("/assets/?download=zip").dispatch();
+        assert_eq!(archive.status(), Status::Ok);
+        let mut start = [0u8; 40];
+        archive.read_exact(&mut start).unwrap();
+        assert_eq!(open_files.load(Ordering::SeqCst), 1);
+        let resp = client.get("/assets/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::ServiceUnavailable);
+
+        // Its files are opened one after the other, so the one permit does for all of them
+        let mut rest = Vec::new();
+        archive.read_to_end(&mut rest).unwrap();
+        let bytes = [&start[..], &rest].concat();
+        let zip = zip::ZipArchive::new(std::io::Cursor::new(bytes)).expect("valid zip");
+        assert_eq!(zip.len(), 5);
+        drop(archive);
+        let resp = client.get("/assets/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_internal_error_description() {
+        let fs = MockFileSystem {
+            open_error: Some("disk on fire"),
+            ..MockFileSystem::new("testdata/assets")
+        };
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::InternalServerError);
+        assert_eq!(resp.into_string(), Some("disk on fire".to_string()));
+
+        // Failing to render a listing is described the same way
+        for stream in [false, true] {
+            let options = OptionsBuilder::new()
+                .allow_directory_listing(true)
+                .stream_directory_listing(stream)
+                .into();
+            let mut server = StaticFileServer::new(LocalFileSystem::new("testdata"), options)
+                .unwrap();
+            let mut hbs = listing_templates();
+            hbs.set_strict_mode(true);
+            for name in ["directory_listing", "directory_listing_header"] {
+                hbs.register_template_string(name, "{{ missing }}").unwrap();
+            }
+            server.templates = Arc::new(hbs);
+            let client = Client::tracked(rocket::build().attach(server)).expect("valid rocket");
+
+            let resp = client.get("/assets/").dispatch();
+            assert_eq!(resp.status(), Status::InternalServerError);
+            let body = resp.into_string().unwrap();
+            assert!(body.contains("missing"), "{}", body);
+        }
+    }
+
+    #[test]
+    fn test_without_range_support() {
+        let fs = MockFileSystem {
+            supports_ranges: false,
+            ..MockFileSystem::new("testdata/assets")
+        };
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("Range", "bytes=5-10"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Accept-Ranges"), Some("none"));
+        assert!(resp.headers().get_one("Content-Range").is_none());
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+
+        let resp = client.head("/hello.txt").dispatch();
+        assert_eq!(resp.headers().get_one("Accept-Ranges"), Some("none"));
+
+        // Opening a backend which can't seek at an offset fails instead of ignoring it
+        let fs = MockFileSystem {
+            supports_ranges: false,
+            ..MockFileSystem::new("testdata/assets")
+        };
+        let rt = rocket::tokio::runtime::Runtime::new().unwrap();
+        assert!(rt.block_on(fs.open("hello.txt", Some(6))).is_err());
+    }
+
+    #[test]
+    fn test_directory_listing_title_and_breadcrumbs() {
+        let fs = LocalFileSystem::new("testdata");
+        let options = OptionsBuilder::new()
+            .prefix("/files")
+            .allow_directory_listing(true)
+            .listing_title("My Site")
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/files/assets/inner/deeper/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.into_string().unwrap();
+        assert!(body.contains("<title>My Site - Directory /assets/inner/deeper/</title>"));
+        assert!(body.contains(r#"<a href="/files/">My Site</a>"#));
+        assert!(body.contains(r#"<a href="/files/assets/">assets</a>"#));
+        assert!(body.contains(r#"<a href="/files/assets/inner/">inner</a>"#));
+        assert!(body.contains(r#"<a href="/files/assets/inner/deeper/">deeper</a>"#));
+
+        // Links are percent-encoded, labels are not
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/breadcrumb-links");
+        std::fs::create_dir_all(dir.join("sub dir#1/50%")).unwrap();
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let resp = client.get("/sub%20dir%231/50%25/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.into_string().unwrap();
+        assert!(body.contains(r#"<a href="/sub%20dir%231/">sub dir#1</a>"#));
+        assert!(body.contains(r#"<a href="/sub%20dir%231/50%25/">50%</a>"#));
+    }
+
+    #[test]
+    fn test_content_type_is_always_set() {
+        let fs = LocalFileSystem::new("testdata/site");
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/data.unknownext").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(
+            resp.headers().get_one("Content-Type"),
+            Some("application/octet-stream")
+        );
+
+        let resp = client
+            .get("/data.unknownext")
+            .header(Header::new("Range", "bytes=0-3"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PartialContent);
+        assert_eq!(
+            resp.headers().get_one("Content-Type"),
+            Some("application/octet-stream")
+        );
+
+        let resp = client.get("/").dispatch();
+        assert_eq!(
+            resp.headers().get_one("Content-Type"),
+            Some("text/html; charset=utf-8")
+        );
+
+        let fs = LocalFileSystem::new("testdata/site");
+        let options = OptionsBuilder::new().default_content_type("text/plain").into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/data.unknownext").dispatch();
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/plain"));
+    }
+
+    #[test]
+    #[cfg(feature = "content_encoding")]
+    fn test_gzip_directory_listing() {
+        let fs = LocalFileSystem::new("testdata");
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client
+            .get("/assets/")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(
+            resp.headers().get_one("Content-Type"),
+            Some("text/html; charset=utf-8")
+        );
+        assert_eq!(resp.headers().get_one("Content-Encoding"), Some("gzip"));
+        assert_eq!(resp.headers().get_one("Vary"), Some("Accept-Encoding"));
+
+        let compressed = resp.into_bytes().unwrap();
+        let mut body = String::new();
+        std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(&compressed[..]), &mut body)
+            .expect("valid gzip body");
+        assert!(body.contains(r#"href="hello.txt""#));
+
+        let resp = client.get("/assets/").dispatch();
+        assert!(resp.headers().get_one("Content-Encoding").is_none());
+        assert!(resp.into_string().unwrap().contains(r#"href="hello.txt""#));
+    }
+
+    #[test]
+    fn test_slow_file_warning() {
+        let slow_files = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        // The file opens right away, but reading it is slow
+        let fs = MockFileSystem {
+            read_delay: Some(Duration::from_millis(50)),
+            ..MockFileSystem::new("testdata/assets")
+        };
+        let reported = Arc::clone(&slow_files);
+        let options = OptionsBuilder::new()
+            .slow_file_warning(Duration::from_millis(50), move |path, elapsed| {
+                reported.lock().unwrap().push((path.to_string(), elapsed));
+            })
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+        {
+            let slow_files = slow_files.lock().unwrap();
+            assert_eq!(slow_files.len(), 1);
+            assert_eq!(slow_files[0].0, "hello.txt");
+            assert!(slow_files[0].1 >= Duration::from_millis(50));
+        }
+
+        // Opening slowly counts as well
+        let fs = MockFileSystem {
+            open_delay: Some(Duration::from_millis(100)),
+            ..MockFileSystem::new("testdata/assets")
+        };
+        let reported = Arc::clone(&slow_files);
+        let options = OptionsBuilder::new()
+            .slow_file_warning(Duration::from_millis(50), move |path, elapsed| {
+                reported.lock().unwrap().push((path.to_string(), elapsed));
+            })
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+        assert_eq!(slow_files.lock().unwrap().len(), 2);
+        slow_files.lock().unwrap().clear();
+
+        // Files served faster than the threshold aren't reported
+        let fs = LocalFileSystem::new("testdata/assets");
+        let reported = Arc::clone(&slow_files);
+        let options = OptionsBuilder::new()
+            .slow_file_warning(Duration::from_secs(10), move |path, elapsed| {
+                reported.lock().unwrap().push((path.to_string(), elapsed));
+            })
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        drop(resp);
+        assert!(slow_files.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cross_origin_isolation() {
+        let fs = LocalFileSystem::new("testdata/site");
+        let options = OptionsBuilder::new().cross_origin_isolation(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/module.wasm").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("application/wasm"));
+        assert_eq!(
+            resp.headers().get_one("Cross-Origin-Opener-Policy"),
+            Some("same-origin")
+        );
+        assert_eq!(
+            resp.headers().get_one("Cross-Origin-Embedder-Policy"),
+            Some("require-corp")
+        );
+
+        let fs = LocalFileSystem::new("testdata/site");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/module.wasm").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.headers().get_one("Cross-Origin-Opener-Policy").is_none());
+        assert!(resp.headers().get_one("Cross-Origin-Embedder-Policy").is_none());
+    }
+
+    #[test]
+    fn test_disable_last_modified() {
+        let bytes = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/target/test.package"));
+        let fs = EmbeddedFileSystem::from_bytes(bytes).expect("valid package");
+        let options = OptionsBuilder::new().disable_last_modified(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.headers().get_one("Last-Modified").is_none());
+
+        let modified: DateTime<Utc> = DateTime::from(
+            std::fs::metadata("testdata/assets/hello.txt")
+                .unwrap()
+                .modified()
+                .unwrap(),
+        );
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new(
+                "If-Modified-Since",
+                modified.format(LAST_MODIFIED_DATE_FORMAT).to_string(),
+            ))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+    }
+
+    #[test]
+    fn test_small_file_threshold() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let options = OptionsBuilder::new().small_file_threshold(1024).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.body().preset_size(), Some(12));
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+
+        let fs = LocalFileSystem::new("src");
+        let options = OptionsBuilder::new().small_file_threshold(1024).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/lib.rs").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.body().preset_size(), None);
+    }
+
+    #[test]
+    fn test_set_local_root() {
+        let fs = Arc::new(LocalFileSystem::new("testdata/assets"));
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(Arc::clone(&fs), Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+
+        fs.set_root("testdata/site");
+        assert_eq!(fs.root().as_path(), Path::new("testdata/site"));
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+        let resp = client.get("/about.html").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(
+            resp.into_bytes(),
+            Some(std::fs::read("testdata/site/about.html").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_local_filesystem_with_roots() {
+        let fs = LocalFileSystem::with_roots(vec![
+            std::path::PathBuf::from("testdata/site"),
+            std::path::PathBuf::from("testdata/assets"),
+        ]);
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        // Only in the second root
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+        let resp = client.get("/inner/other.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+
+        // Only in the first root
+        let resp = client.get("/about.html").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+
+        let resp = client.get("/missing.txt").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+
+        let body = client.get("/").dispatch().into_string().unwrap();
+        assert!(body.contains(r#"href="about.html""#));
+        assert!(body.contains(r#"href="hello.txt""#));
+        assert!(body.contains(r#"href="inner/""#));
+    }
+
+    #[test]
+    fn test_mapped_filesystem() {
+        let fs = MappedFileSystem::new(LocalFileSystem::new("testdata"), "public", "site/docs");
+        let options = OptionsBuilder::new().into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/public/guide.html").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(
+            resp.into_bytes(),
+            Some(std::fs::read("testdata/site/docs/guide.html").unwrap())
+        );
+
+        // Paths outside of the mapping are passed through
+        let resp = client.get("/assets/hello.txt").dispatch();
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+    }
+
+    #[test]
+    fn test_flattened_filesystem() {
+        let rt = rocket::tokio::runtime::Runtime::new().unwrap();
+        let fs = rt
+            .block_on(FlattenedFileSystem::new(LocalFileSystem::new("testdata/assets")))
+            .expect("no name collisions");
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/deep.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(
+            resp.into_bytes(),
+            Some(std::fs::read("testdata/assets/inner/deeper/deep.txt").unwrap())
+        );
+
+        // The nested paths themselves are gone
+        let resp = client.get("/inner/other.txt").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+
+        let body = client.get("/").dispatch().into_string().unwrap();
+        assert!(body.contains(r#"href="other.txt""#));
+        assert!(!body.contains(r#"href="inner/""#));
+
+        // testdata/mime/module.wasm and testdata/site/module.wasm
+        let err = rt
+            .block_on(FlattenedFileSystem::new(LocalFileSystem::new("testdata")))
+            .err()
+            .expect("name collision");
+        assert!(err.to_string().contains("module.wasm"));
+    }
+
+    #[test]
+    fn test_charset_map() {
+        let fs = LocalFileSystem::new("testdata/site");
+        let mut charsets = std::collections::HashMap::new();
+        charsets.insert("text/*".to_string(), "utf-8".to_string());
+        charsets.insert("text/css".to_string(), "windows-1252".to_string());
+        let options = OptionsBuilder::new().charset_map(charsets).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/about.html").dispatch();
+        assert_eq!(
+            resp.headers().get_one("Content-Type"),
+            Some("text/html; charset=utf-8")
+        );
+
+        let resp = client.get("/style.css").dispatch();
+        assert_eq!(
+            resp.headers().get_one("Content-Type"),
+            Some("text/css; charset=windows-1252")
+        );
+
+        // Types outside of the map are left alone
+        let resp = client.get("/module.wasm").dispatch();
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("application/wasm"));
+    }
+
+    #[test]
+    fn test_query_string_is_ignored() {
+        let fs = LocalFileSystem::new("src");
+        let options = OptionsBuilder::new().prefix("/test").into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/test/lib.rs?v=123").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(
+            resp.into_bytes(),
+            Some(std::fs::read("src/lib.rs").unwrap())
+        );
+
+        let fs = LocalFileSystem::new("testdata/assets");
+        let options = OptionsBuilder::new().into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt?v=1&b=2").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+    }
+
+    #[test]
+    fn test_query_in_etag() {
+        let etags = |query_in_etag: bool| {
+            let fs = LocalFileSystem::new("testdata/assets");
+            let options = OptionsBuilder::new().query_in_etag(query_in_etag).into();
+            let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+            let client = Client::tracked(rocket).expect("valid rocket");
+            ["/hello.txt", "/hello.txt?v=abc", "/hello.txt?v=def"].map(|uri| {
+                let resp = client.get(uri).dispatch();
+                assert_eq!(resp.status(), Status::Ok);
+                let etag = resp.headers().get_one("ETag").unwrap().to_string();
+                assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+                etag
+            })
+        };
+
+        let [plain, abc, def] = etags(true);
+        assert_ne!(plain, abc);
+        assert_ne!(abc, def);
+        assert_eq!(etags(true), [plain.clone(), abc, def]);
+
+        let [plain_off, abc, def] = etags(false);
+        assert_eq!(plain_off, plain);
+        assert_eq!(abc, plain);
+        assert_eq!(def, plain);
+    }
+
+    #[test]
+    fn test_disposition_by_mime() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/disposition");
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["doc.pdf", "files.zip", "photo.png", "notes.txt"] {
+            std::fs::write(dir.join(name), "data").unwrap();
+        }
+
+        let map = vec![
+            ("application/pdf".to_string(), Disposition::Inline),
+            ("application/zip".to_string(), Disposition::Attachment),
+            ("image/*".to_string(), Disposition::Attachment),
+        ];
+        let options = OptionsBuilder::new()
+            .disposition_by_mime(map.into_iter().collect())
+            .into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let disposition = |uri: &str| {
+            let resp = client.get(uri).dispatch();
+            assert_eq!(resp.status(), Status::Ok);
+            resp.headers().get_one("Content-Disposition").map(str::to_string)
+        };
+
+        assert_eq!(disposition("/doc.pdf").as_deref(), Some("inline"));
+        assert_eq!(
+            disposition("/files.zip").as_deref(),
+            Some(r#"attachment; filename="files.zip""#)
+        );
+        assert_eq!(
+            disposition("/photo.png").as_deref(),
+            Some(r#"attachment; filename="photo.png""#)
+        );
+        assert_eq!(disposition("/notes.txt"), None);
+    }
+
+    #[test]
+    fn test_attachment() {
+        assert_eq!(attachment("a b.zip"), r#"attachment; filename="a b.zip""#);
+        assert_eq!(attachment(r#"say "hi".txt"#), r#"attachment; filename="say \"hi\".txt""#);
+        assert_eq!(attachment("größe.pdf"), "attachment; filename*=UTF-8''gr%C3%B6%C3%9Fe.pdf");
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        assert!(etag_matches(r#""a""#, r#""a""#, false));
+        assert!(etag_matches(r#"W/"a""#, r#""a""#, false));
+        assert!(etag_matches(r#""b", W/"a""#, r#"W/"a""#, false));
+        assert!(!etag_matches(r#""b""#, r#""a""#, false));
+
+        assert!(etag_matches(r#""b", "a""#, r#""a""#, true));
+        assert!(etag_matches("*", r#""a""#, true));
+        assert!(!etag_matches(r#"W/"a""#, r#""a""#, true));
+        assert!(!etag_matches(r#""a""#, r#"W/"a""#, true));
+    }
+
+    #[test]
+    fn test_etag_strength() {
+        let client = |strength: Option<EtagStrength>| {
+            let mut options = OptionsBuilder::new();
+            if let Some(strength) = strength {
+                options = options.etag_strength(strength);
+            }
+            let fs = LocalFileSystem::new("testdata/assets");
+            let rocket = rocket::build().attach(StaticFileServer::new(fs, options.into()).unwrap());
+            Client::tracked(rocket).expect("valid rocket")
+        };
+        let request = |client: &Client, if_none_match: &str| {
+            let resp = client
+                .get("/hello.txt")
+                .header(Header::new("If-None-Match", if_none_match.to_string()))
+                .dispatch();
+            resp.status()
+        };
+
+        let weak = client(Some(EtagStrength::Weak));
+        let etag = weak.get("/hello.txt").dispatch().headers().get_one("ETag").unwrap().to_string();
+        assert!(etag.starts_with(r#"W/""#), "{}", etag);
+        let tag = etag.trim_start_matches("W/").to_string();
+        assert_eq!(request(&weak, &etag), Status::NotModified);
+        assert_eq!(request(&weak, &tag), Status::NotModified);
+
+        let strong = client(Some(EtagStrength::Strong));
+        let resp = strong.get("/hello.txt").dispatch();
+        assert_eq!(resp.headers().get_one("ETag"), Some(tag.as_str()));
+        assert_eq!(request(&strong, &tag), Status::NotModified);
+        assert_eq!(request(&strong, &etag), Status::Ok);
+
+        // By default tags are strong but compared weakly
+        let default = client(None);
+        let resp = default.get("/hello.txt").dispatch();
+        assert_eq!(resp.headers().get_one("ETag"), Some(tag.as_str()));
+        assert_eq!(request(&default, &etag), Status::NotModified);
+    }
+
+    #[test]
+    fn test_referer_origin() {
+        assert_eq!(referer_origin("https://example.com/page"), Some("https://example.com"));
+        assert_eq!(referer_origin("http://localhost:8000"), Some("http://localhost:8000"));
+        assert_eq!(referer_origin("https://example.com?q=1"), Some("https://example.com"));
+        assert_eq!(referer_origin("https:///page"), None);
+        assert_eq!(referer_origin("example.com/page"), None);
+    }
+
+    #[test]
+    fn test_source_map_origins() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/source-map-origins");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("app.js"), "app();").unwrap();
+        std::fs::write(dir.join("app.js.map"), r#"{"version":3}"#).unwrap();
+        std::fs::write(dir.join("app.js.map.gz"), "not really gzip").unwrap();
+        std::fs::write(dir.join("lib.js.MAP"), r#"{"version":3}"#).unwrap();
+        std::fs::write(dir.join("vendor.js.map.br"), "not really brotli").unwrap();
+
+        let options = OptionsBuilder::new()
+            .source_map_origins(vec!["https://internal.example.com".to_string()])
+            .precompressed_variants(true)
+            .archive_download(true)
+            .into();
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let get = |header: Option<(&'static str, &'static str)>| {
+            let mut req = client.get("/app.js.map");
+            if let Some((name, value)) = header {
+                req = req.header(Header::new(name, value));
+            }
+            req.dispatch().status()
+        };
+
+        assert_eq!(get(Some(("Origin", "https://internal.example.com"))), Status::Ok);
+        assert_eq!(get(Some(("Referer", "https://internal.example.com/app/"))), Status::Ok);
+        assert_eq!(get(Some(("Referer", "https://example.com/app/"))), Status::NotFound);
+        assert_eq!(get(Some(("Origin", "https://internal.example.com.evil"))), Status::NotFound);
+        assert_eq!(get(None), Status::NotFound);
+        // Other files are served to everyone
+        assert_eq!(client.get("/app.js").dispatch().status(), Status::Ok);
+
+        // Neither the case nor a compressed copy gets around the check
+        for path in ["/lib.js.MAP", "/app.js.map.gz", "/vendor.js.map.br"] {
+            assert_eq!(client.get(path).dispatch().status(), Status::NotFound, "{}", path);
+        }
+        let resp = client
+            .get("/app.js.map")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+        let resp = client
+            .get("/lib.js.MAP")
+            .header(Header::new("Origin", "https://internal.example.com"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+
+        // Archives only have them for the allowed origins as well
+        let names = |origin: Option<&'static str>| {
+            let mut req = client.get("/?download=zip");
+            if let Some(origin) = origin {
+                req = req.header(Header::new("Origin", origin));
+            }
+            let bytes = req.dispatch().into_bytes().unwrap();
+            let zip = zip::ZipArchive::new(std::io::Cursor::new(bytes)).expect("valid zip");
+            let mut names: Vec<String> = zip.file_names().map(str::to_string).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(None), vec!["app.js"]);
+        assert_eq!(names(Some("https://internal.example.com")).len(), 5);
+    }
+
+    #[test]
+    fn test_inject_banner() {
+        let inject =
+            |html: &str| String::from_utf8(inject_banner(html.into(), "<b>!</b>")).unwrap();
+        assert_eq!(inject("<body>a</body></html>"), "<body>a<b>!</b></body></html>");
+        assert_eq!(inject("<BODY>a</BODY>"), "<BODY>a<b>!</b></BODY>");
+        assert_eq!(inject("<p>a</p>"), "<p>a</p><b>!</b>");
+    }
+
+    #[test]
+    fn test_html_banner() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/html-banner");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("page.html"), "<html><body><p>page</p></body></html>").unwrap();
+        std::fs::write(dir.join("app.js"), "document.write('</body>');").unwrap();
+        std::fs::write(dir.join("page.html.gz"), "not really gzip").unwrap();
+
+        let banner = r#"<div class="banner">Maintenance at 10:00</div>"#;
+        let client = |banner: Option<&str>| {
+            let options = OptionsBuilder::new()
+                .html_banner(banner.map(str::to_string))
+                .precompressed_variants(true)
+                .into();
+            let rocket = rocket::build()
+                .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+            Client::tracked(rocket).expect("valid rocket")
+        };
+        let client_plain = client(None);
+        let client = client(Some(banner));
+
+        let resp = client.get("/page.html").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(
+            resp.into_string(),
+            Some(format!("<html><body><p>page</p>{}</body></html>", banner))
+        );
+
+        let resp = client.get("/app.js").dispatch();
+        assert_eq!(resp.into_string(), Some("document.write('</body>');".to_string()));
+
+        // Ranges are of the file itself
+        let resp = client
+            .get("/page.html")
+            .header(Header::new("Range", "bytes=0-5"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PartialContent);
+        assert_eq!(resp.into_string(), Some("<html>".to_string()));
+
+        // Pages cached before the banner was set are sent again, with it
+        let resp = client_plain.get("/page.html").dispatch();
+        let cached = resp.headers().get_one("ETag").unwrap().to_string();
+        let resp = client
+            .get("/page.html")
+            .header(Header::new("If-None-Match", cached))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.into_string().unwrap().contains(banner));
+        let js = |client: &Client| {
+            let resp = client.get("/app.js").dispatch();
+            resp.headers().get_one("ETag").unwrap().to_string()
+        };
+        assert_eq!(js(&client), js(&client_plain));
+
+        // The precompressed variant doesn't have the banner, so it isn't sent
+        let resp = client
+            .get("/page.html")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(resp.headers().get_one("Content-Encoding"), None);
+        assert!(resp.into_string().unwrap().contains(banner));
+    }
+
+    #[cfg(feature = "zip_archive")]
+    #[test]
+    fn test_zip_filesystem() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let mut writer = ::zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let stored = SimpleFileOptions::default()
+            .compression_method(::zip::CompressionMethod::Stored);
+        let deflated = SimpleFileOptions::default()
+            .compression_method(::zip::CompressionMethod::Deflated);
+        writer.start_file("hello.txt", stored).unwrap();
+        writer.write_all(b"Hello, zip!\n").unwrap();
+        writer.start_file("inner/deep.txt", deflated).unwrap();
+        writer.write_all(&b"deep ".repeat(100)).unwrap();
+        writer.start_file("../escape.txt", stored).unwrap();
+        writer.write_all(b"outside").unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let fs = ZipFileSystem::from_vec(bytes).expect("valid zip");
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("text/plain"));
+        assert_eq!(resp.into_string(), Some("Hello, zip!\n".to_string()));
+
+        // Deflated entries are decompressed, ranges are cut out of the result
+        let resp = client
+            .get("/inner/deep.txt")
+            .header(Header::new("Range", "bytes=5-9"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PartialContent);
+        assert_eq!(resp.headers().get_one("Content-Range"), Some("bytes 5-9/500"));
+        assert_eq!(resp.into_string(), Some("deep ".to_string()));
+
+        let body = client.get("/").dispatch().into_string().unwrap();
+        assert!(body.contains(r#"href="hello.txt""#));
+        assert!(body.contains(r#"href="inner/""#));
+        assert!(!body.contains("escape.txt"));
+
+        let resp = client.get("/inner/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.into_string().unwrap().contains(r#"href="deep.txt""#));
+
+        assert_eq!(client.get("/escape.txt").dispatch().status(), Status::NotFound);
+        assert!(ZipFileSystem::from_vec(b"not a zip".to_vec()).is_err());
+    }
 
-        let last_modified = resp.headers()
-            .get_one("Last-Modified")
-            .expect("no last modified header")
-            .to_owned();
+    #[test]
+    fn test_server_header() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let options = OptionsBuilder::new()
+            .server_header(Some("static".to_string()))
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Server"), Some("static"));
+
+        let fs = LocalFileSystem::new("testdata/assets");
+        let options = OptionsBuilder::new().server_header(None).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.headers().get_one("Server").is_none());
+    }
+
+    #[test]
+    fn test_immutable_prefix() {
+        let fs = LocalFileSystem::new("testdata");
+        let options = OptionsBuilder::new().immutable_prefix("/assets/inner/").into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/assets/inner/other.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(
+            resp.headers().get_one("Cache-Control"),
+            Some("public, max-age=31536000, immutable")
+        );
+
+        let resp = client.get("/assets/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.headers().get_one("Cache-Control").is_none());
+    }
+
+    #[test]
+    fn test_no_store_pattern() {
+        let fs = LocalFileSystem::new("testdata");
+        let options = OptionsBuilder::new()
+            .no_store_pattern(Regex::new("^/assets/inner/").unwrap())
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/assets/inner/other.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Cache-Control"), Some("no-store"));
+        assert!(resp.headers().get_one("Last-Modified").is_none());
+        assert!(resp.headers().get_one("ETag").is_none());
+
+        let resp = client.get("/assets/hello.txt").dispatch();
+        assert!(resp.headers().get_one("Cache-Control").is_none());
+        assert!(resp.headers().get_one("Last-Modified").is_some());
+    }
+
+    #[test]
+    fn test_not_modified_keeps_validators() {
+        let fs = LocalFileSystem::new("testdata");
+        let options = OptionsBuilder::new().immutable_prefix("/assets/").into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/assets/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let etag = resp.headers().get_one("ETag").unwrap().to_string();
+        let last_modified = resp.headers().get_one("Last-Modified").unwrap().to_string();
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
 
-        // Check for NotModified on second response with If-Modified-Since header
         let resp = client
-            .get("/test/lib.rs")
+            .get("/assets/hello.txt")
+            .header(Header::new("If-None-Match", etag.clone()))
+            .dispatch();
+        assert_eq!(resp.status(), Status::NotModified);
+        assert_eq!(resp.headers().get_one("ETag"), Some(etag.as_str()));
+        assert_eq!(resp.headers().get_one("Last-Modified"), Some(last_modified.as_str()));
+        assert_eq!(
+            resp.headers().get_one("Cache-Control"),
+            Some("public, max-age=31536000, immutable")
+        );
+
+        let resp = client
+            .get("/assets/hello.txt")
+            .header(Header::new("If-Modified-Since", last_modified.clone()))
+            .dispatch();
+        assert_eq!(resp.status(), Status::NotModified);
+        assert_eq!(resp.headers().get_one("ETag"), Some(etag.as_str()));
+
+        // A differing ETag wins over a matching date
+        let resp = client
+            .get("/assets/hello.txt")
+            .header(Header::new("If-None-Match", r#""other", W/"another""#))
+            .header(Header::new("If-Modified-Since", last_modified))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_head_not_modified() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.head("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let last_modified = resp.headers().get_one("Last-Modified").unwrap().to_string();
+        let etag = resp.headers().get_one("ETag").unwrap().to_string();
+
+        let resp = client
+            .head("/hello.txt")
             .header(Header::new("If-Modified-Since", last_modified))
             .dispatch();
         assert_eq!(resp.status(), Status::NotModified);
+        assert!(resp.headers().get_one("Content-Length").is_none());
 
-        // Test for Range support
-        let mut resp = client
-            .get("/test/lib.rs")
-            .header(Header::new("Range", "bytes=5-10"))
+        let resp = client
+            .head("/hello.txt")
+            .header(Header::new("If-None-Match", etag))
+            .dispatch();
+        assert_eq!(resp.status(), Status::NotModified);
+
+        let resp = client
+            .head("/hello.txt")
+            .header(Header::new("If-Modified-Since", "Thu, 01 Jan 1970 00:00:00 GMT"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_if_match() {
+        let client = |options: OptionsBuilder| {
+            let fs = LocalFileSystem::new("testdata/assets");
+            let rocket =
+                rocket::build().attach(StaticFileServer::new(fs, options.into()).unwrap());
+            Client::tracked(rocket).expect("valid rocket")
+        };
+        let client_default = client(OptionsBuilder::new());
+        let etag = client_default.get("/hello.txt").dispatch();
+        let etag = etag.headers().get_one("ETag").unwrap().to_string();
+
+        for if_match in [etag.clone(), format!(r#""other", {}"#, etag), "*".to_string()] {
+            let resp = client_default
+                .get("/hello.txt")
+                .header(Header::new("If-Match", if_match))
+                .dispatch();
+            assert_eq!(resp.status(), Status::Ok);
+            assert_eq!(resp.into_string().unwrap(), "Hello World!");
+        }
+
+        let resp = client_default
+            .get("/hello.txt")
+            .header(Header::new("If-Match", r#""other""#))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PreconditionFailed);
+        assert_eq!(resp.into_string().unwrap(), "");
+
+        // If-Match compares strongly, so weak ETags only match `*`
+        let client_weak = client(OptionsBuilder::new().etag_strength(EtagStrength::Weak));
+        for (if_match, status) in [
+            (format!("W/{}", etag), Status::PreconditionFailed),
+            ("*".to_string(), Status::Ok),
+        ] {
+            let resp = client_weak
+                .get("/hello.txt")
+                .header(Header::new("If-Match", if_match))
+                .dispatch();
+            assert_eq!(resp.status(), status);
+        }
+    }
+
+    #[test]
+    fn test_no_range_pattern() {
+        let fs = LocalFileSystem::new("testdata");
+        let options = OptionsBuilder::new()
+            .no_range_pattern(Regex::new(r"\.txt$").unwrap())
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client
+            .get("/assets/hello.txt")
+            .header(Header::new("Range", "bytes=6-10"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Accept-Ranges"), Some("none"));
+        assert!(resp.headers().get_one("Content-Range").is_none());
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+
+        let resp = client.head("/assets/hello.txt").dispatch();
+        assert_eq!(resp.headers().get_one("Accept-Ranges"), Some("none"));
+
+        let resp = client
+            .get("/site/style.css")
+            .header(Header::new("Range", "bytes=0-3"))
             .dispatch();
         assert_eq!(resp.status(), Status::PartialContent);
-        assert_eq!(resp.headers().get_one("Content-Length"), Some("6"));
-        let body = resp.body_bytes().unwrap();
-        assert_eq!(body.len(), 6);
+        assert_eq!(resp.headers().get_one("Accept-Ranges"), Some("bytes"));
+        let css = std::fs::read("testdata/site/style.css").unwrap();
+        assert_eq!(resp.into_bytes(), Some(css[..4].to_vec()));
     }
 
     #[test]
-    fn test_with_embedded_filesystem() {
-        let bytes = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/target/test.package"));
-        let fs = EmbeddedFileSystem::from_bytes(bytes);
+    fn test_transform() {
+        let fs = LocalFileSystem::new("testdata/site");
+        let options = OptionsBuilder::new()
+            .transform(|path, bytes| {
+                if !path.ends_with(".html") {
+                    return bytes;
+                }
+                let html = String::from_utf8(bytes).unwrap();
+                html.replacen("<head>", r#"<head><base href="/app/">"#, 1).into_bytes()
+            })
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
 
-        match fs {
-            Ok(fs) => {
-                let options = OptionsBuilder::new().prefix("/test").into();
-                let rocket = rocket::ignite().attach(StaticFileServer::new(fs, options).unwrap());
-                let client = Client::new(rocket).expect("valid rocket");
+        let original = std::fs::read_to_string("testdata/site/about.html").unwrap();
+        let resp = client
+            .get("/about.html")
+            .header(Header::new("Range", "bytes=0-5"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Accept-Ranges"), Some("none"));
+        let body = resp.into_string().unwrap();
+        assert!(body.contains(r#"<base href="/app/">"#));
+        assert_eq!(body.len(), original.len() + r#"<base href="/app/">"#.len());
 
-                let mut resp = client.get("/test/hello.txt").dispatch();
-                assert_eq!(resp.status(), Status::Ok);
-                assert_eq!(
-                    resp.headers()
-                        .get_one("Content-Type")
-                        .expect("no content type"),
-                    "text/plain"
-                );
-                assert_eq!(resp.body_string(), Some("Hello World!".to_string()));
+        // HEAD doesn't read the file, so it can't tell the transformed length
+        let resp = client.head("/about.html").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.headers().get_one("Content-Length").is_none());
 
-                let last_modified = resp.headers()
-                    .get_one("Last-Modified")
-                    .expect("no last modified header")
-                    .to_owned();
+        let resp = client.get("/data.unknownext").dispatch();
+        assert_eq!(
+            resp.into_bytes(),
+            Some(std::fs::read("testdata/site/data.unknownext").unwrap())
+        );
+    }
 
-                // Check for NotModified on second response with If-Modified-Since header
-                let resp = client
-                    .get("/test/hello.txt")
-                    .header(Header::new("If-Modified-Since", last_modified))
-                    .dispatch();
-                assert_eq!(resp.status(), Status::NotModified);
+    #[test]
+    fn test_canonical_host() {
+        use rocket::http::uri::Host;
 
-                // Test for Range support
-                let mut resp = client
-                    .get("/test/hello.txt")
-                    .header(Header::new("Range", "bytes=5-10"))
-                    .dispatch();
-                assert_eq!(resp.status(), Status::PartialContent);
-                assert_eq!(resp.headers().get_one("Content-Length"), Some("6"));
-                assert_eq!(resp.body_string(), Some(" World".to_string()));
+        let fs = LocalFileSystem::new("testdata/assets");
+        let options = OptionsBuilder::new()
+            .canonical_host(Some("example.com".to_string()))
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
 
-                let mut resp = client
-                    .get("/test/hello.txt")
-                    .header(Header::new("Range", "bytes=0-"))
-                    .dispatch();
-                assert_eq!(resp.status(), Status::PartialContent);
-                assert_eq!(resp.body_bytes().unwrap().len(), 12);
-            }
-            Err(e) => panic!(format!(
-                "unable to load test.package, maybe you just need to re-run the test: {}",
-                e
-            )),
+        let mut req = client.get("/hello.txt?v=1");
+        req.inner_mut().set_host(Host::parse("www.example.com").unwrap());
+        let resp = req.dispatch();
+        assert_eq!(resp.status(), Status::MovedPermanently);
+        assert_eq!(
+            resp.headers().get_one("Location"),
+            Some("http://example.com/hello.txt?v=1")
+        );
+
+        let mut req = client.get("/hello.txt");
+        req.inner_mut().set_host(Host::parse("example.com").unwrap());
+        let resp = req.dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+    }
+
+    #[test]
+    fn test_not_found_response() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let options = OptionsBuilder::new()
+            .not_found_response(Status::NotFound, r#"{"error":"not found"}"#, "application/json")
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/missing.txt").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("application/json"));
+        assert_eq!(resp.into_string(), Some(r#"{"error":"not found"}"#.to_string()));
+
+        // Directories without listing are missing as well
+        let resp = client.get("/inner/").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+        assert_eq!(resp.headers().get_one("Content-Type"), Some("application/json"));
+    }
+
+    #[test]
+    fn test_modern_content_types() {
+        let fs = LocalFileSystem::new("testdata/mime");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        for (path, content_type) in &[
+            ("/app.mjs", "text/javascript"),
+            ("/image.webp", "image/webp"),
+            ("/image.avif", "image/avif"),
+            ("/font.woff2", "font/woff2"),
+            ("/module.wasm", "application/wasm"),
+            ("/site.webmanifest", "application/manifest+json"),
+        ] {
+            let resp = client.get(*path).dispatch();
+            assert_eq!(resp.status(), Status::Ok);
+            assert_eq!(resp.headers().get_one("Content-Type"), Some(*content_type), "{}", path);
         }
     }
 
     #[test]
-    fn test_directory_listing_with_local_filesystem() {
-        let fs = LocalFileSystem::new("");
-        let options = OptionsBuilder::new().allow_directory_listing(true).into();
-        let rocket = rocket::ignite().attach(StaticFileServer::new(fs, options).unwrap());
-        let client = Client::new(rocket).expect("valid rocket");
+    fn test_throttle_bytes_per_sec() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/throttle");
+        std::fs::create_dir_all(&dir).unwrap();
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(dir.join("large.bin"), &data).unwrap();
 
-        let resp = client.get("/src").dispatch();
-        assert_eq!(resp.status(), Status::Found);
-        assert_eq!(resp.headers().get_one("Location"), Some("/src/"));
+        let get = |options: Options| {
+            let rocket = rocket::build()
+                .attach(StaticFileServer::new(LocalFileSystem::new(&dir), options).unwrap());
+            let client = Client::tracked(rocket).expect("valid rocket");
+            let started = std::time::Instant::now();
+            let body = client.get("/large.bin").dispatch().into_bytes();
+            (body, started.elapsed())
+        };
+
+        let (body, unthrottled) = get(Options::default());
+        assert_eq!(body.as_ref(), Some(&data));
+
+        // 20 kB at 20 kB/s, where the last of the 2 kB chunks is due after 0.9 seconds
+        let options = OptionsBuilder::new()
+            .throttle_bytes_per_sec(Some(20_000))
+            .into();
+        let (body, elapsed) = get(options);
+        assert_eq!(body, Some(data));
+        assert!(elapsed >= Duration::from_millis(800), "{:?}", elapsed);
+        assert!(elapsed > unthrottled);
+    }
+
+    #[test]
+    fn test_uppercase_extensions() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/uppercase-extensions");
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in &["photo.jpg", "photo.JPG", "photo.Jpg", "image.WEBP", "README"] {
+            std::fs::write(dir.join(name), "data").unwrap();
+        }
+
+        let rocket = rocket::build()
+            .attach(StaticFileServer::new(LocalFileSystem::new(&dir), Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        for (path, content_type) in &[
+            ("/photo.jpg", "image/jpeg"),
+            ("/photo.JPG", "image/jpeg"),
+            ("/photo.Jpg", "image/jpeg"),
+            ("/image.WEBP", "image/webp"),
+            ("/README", "application/octet-stream"),
+        ] {
+            let resp = client.get(*path).dispatch();
+            assert_eq!(resp.status(), Status::Ok);
+            assert_eq!(resp.headers().get_one("Content-Type"), Some(*content_type), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_effective_options() {
+        let options = OptionsBuilder::new()
+            .prefix("/static")
+            .allow_directory_listing(true)
+            .directory_listing_default_index("index.html")
+            .charset_map(vec![("text/*".to_string(), "utf-8".to_string())].into_iter().collect())
+            .no_store_pattern(Regex::new("^/static/private/").unwrap())
+            .into();
+        let server = StaticFileServer::new(LocalFileSystem::new("testdata"), options).unwrap();
 
-        let mut resp = client.get("/src/").dispatch();
+        let effective = server.effective_options();
+        assert_eq!(effective.prefix, "/static/");
+        assert!(effective.allow_directory_listing);
+        assert_eq!(effective.directory_listing_default_index.as_deref(), Some("index.html"));
+        assert_eq!(
+            effective.mime_overrides.get("wasm").map(String::as_str),
+            Some("application/wasm")
+        );
+        assert!(!effective.request_filter);
+
+        let json = serde_json::to_value(&effective).unwrap();
+        assert_eq!(json["prefix"], "/static/");
+        assert_eq!(json["allow_directory_listing"], true);
+        assert_eq!(json["charset_map"]["text/*"], "utf-8");
+        assert_eq!(json["no_store_pattern"], "^/static/private/");
+        assert_eq!(json["max_concurrent_opens"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_effective_options_complete() {
+        // Every field of `Options` has to show up, so options can't be left out of the snapshot
+        let source = include_str!("options.rs");
+        let start = source.find("pub struct Options {").unwrap();
+        let end = start + source[start..].find("\n}").unwrap();
+        let fields = Regex::new(r"(?m)^    ([a-z_]+):").unwrap();
+        let fields: Vec<&str> = fields
+            .captures_iter(&source[start..end])
+            .map(|field| field.get(1).unwrap().as_str())
+            .collect();
+        assert!(fields.len() > 40);
+
+        let options = OptionsBuilder::new().server_header(None).into();
+        let server = StaticFileServer::new(LocalFileSystem::new("testdata"), options).unwrap();
+        let json = serde_json::to_value(server.effective_options()).unwrap();
+        for field in fields {
+            assert!(json.get(field).is_some(), "{} is missing from EffectiveOptions", field);
+        }
+        assert_eq!(json["server_header"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_request_filter() {
+        let fs = LocalFileSystem::new("testdata/assets");
+        let options = OptionsBuilder::new()
+            .request_filter(|request| request.headers().get_one("X-Token") == Some("secret"))
+            .into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/hello.txt").dispatch();
+        assert_eq!(resp.status(), Status::Forbidden);
+        assert_eq!(resp.into_string(), Some(String::new()));
+
+        let resp = client
+            .get("/hello.txt")
+            .header(Header::new("X-Token", "secret"))
+            .dispatch();
         assert_eq!(resp.status(), Status::Ok);
-        let body = resp.body_string().unwrap();
-        assert!(body.contains(r#"href="lib.rs""#));
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+    }
+
+    #[test]
+    #[cfg(feature = "content_encoding")]
+    fn test_head_matches_compressed_get() {
+        let fs = LocalFileSystem::new(".");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let get = client
+            .get("/src/lib.rs")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        let head = client
+            .head("/src/lib.rs")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(get.status(), Status::Ok);
+        assert_eq!(head.status(), Status::Ok);
+        for name in &[
+            "Content-Type",
+            "Content-Encoding",
+            "Content-Length",
+            "Vary",
+            "ETag",
+            "Last-Modified",
+            "Accept-Ranges",
+        ] {
+            assert_eq!(
+                head.headers().get_one(name),
+                get.headers().get_one(name),
+                "{} differs",
+                name
+            );
+        }
+        assert_eq!(head.headers().get_one("Content-Encoding"), Some("gzip"));
+        assert_eq!(head.headers().get_one("Vary"), Some("Accept-Encoding"));
+        assert!(head.headers().get_one("Content-Length").is_none());
+        assert!(head.headers().get_one("ETag").unwrap().starts_with("W/"));
+    }
+
+    #[test]
+    #[cfg(feature = "content_encoding")]
+    fn test_compress_min_size() {
+        let fs = LocalFileSystem::new(".");
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, Options::default()).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client
+            .get("/testdata/assets/hello.txt")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.headers().get_one("Content-Encoding").is_none());
+        assert_eq!(resp.into_string(), Some("Hello World!".to_string()));
+
+        let resp = client
+            .head("/testdata/assets/hello.txt")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert!(resp.headers().get_one("Content-Encoding").is_none());
+        assert_eq!(resp.headers().get_one("Content-Length"), Some("12"));
+
+        let resp = client
+            .get("/src/lib.rs")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one("Content-Encoding"), Some("gzip"));
+
+        // Encodings with a quality of 0 are refused
+        for accept_encoding in ["gzip;q=0", "gzip; q=0.0, deflate;q=0"] {
+            let resp = client
+                .get("/src/lib.rs")
+                .header(Header::new("Accept-Encoding", accept_encoding))
+                .dispatch();
+            assert!(resp.headers().get_one("Content-Encoding").is_none());
+        }
+        let resp = client
+            .get("/src/lib.rs")
+            .header(Header::new("Accept-Encoding", "gzip;q=0, deflate"))
+            .dispatch();
+        assert_eq!(resp.headers().get_one("Content-Encoding"), Some("deflate"));
     }
 
     #[test]
-    fn test_parse_range_header() {
-        let range: Range = "bytes=0-1023"
-            .parse()
-            .expect("unable to parse Range header");
-        assert_eq!(range.start, 0);
-        assert_eq!(range.end, Some(1023));
-        assert_eq!(range.unit, "bytes");
+    fn test_directory_listing_sortable_metadata() {
+        let fs = LocalFileSystem::new("testdata");
+        let options = OptionsBuilder::new().allow_directory_listing(true).into();
+        let rocket = rocket::build().attach(StaticFileServer::new(fs, options).unwrap());
+        let client = Client::tracked(rocket).expect("valid rocket");
+
+        let resp = client.get("/assets/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.into_string().unwrap();
+        let row = Regex::new(concat!(
+            r#"<tr data-size="12" data-modified="(\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}Z)">"#,
+            r#"\s*<td class="name"><a href="hello.txt">"#,
+        ))
+        .unwrap();
+        let iso_modified = &row.captures(&body).expect("row with metadata")[1];
+        assert!(body.contains(&format!(r#"<time datetime="{}">"#, iso_modified)));
+    }
+
+    /// Wraps a LocalFileSystem to simulate backends with different capabilities.
+    struct MockFileSystem {
+        inner: LocalFileSystem,
+        supports_ranges: bool,
+        open_delay: Option<Duration>,
+        read_delay: Option<Duration>,
+        open_error: Option<&'static str>,
+        stats: Arc<AtomicUsize>,
+        is_file_calls: Arc<AtomicUsize>,
+        modified: Option<SystemTime>,
+        open_files: Arc<AtomicUsize>,
+        seeking_opens: Arc<AtomicUsize>,
+        opens: Arc<AtomicUsize>,
+    }
+
+    /// A file of the MockFileSystem, counted as open until it's dropped. Every read waits
+    /// for `read_delay` first.
+    struct MockFile {
+        file: rocket::tokio::fs::File,
+        open_files: Arc<AtomicUsize>,
+        read_delay: Option<Duration>,
+        delay: Option<std::pin::Pin<Box<rocket::tokio::time::Sleep>>>,
+    }
+
+    impl AsyncRead for MockFile {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut rocket::tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            use std::future::Future;
+
+            let this = &mut *self;
+            if let Some(read_delay) = this.read_delay {
+                let delay = this
+                    .delay
+                    .get_or_insert_with(|| Box::pin(rocket::tokio::time::sleep(read_delay)));
+                if delay.as_mut().poll(cx).is_pending() {
+                    return std::task::Poll::Pending;
+                }
+                this.delay = None;
+            }
+            std::pin::Pin::new(&mut this.file).poll_read(cx, buf)
+        }
+    }
+
+    impl Drop for MockFile {
+        fn drop(&mut self) {
+            self.open_files.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    impl MockFileSystem {
+        fn new(path: &str) -> Self {
+            MockFileSystem {
+                inner: LocalFileSystem::new(path),
+                supports_ranges: true,
+                open_delay: None,
+                read_delay: None,
+                open_error: None,
+                stats: Arc::new(AtomicUsize::new(0)),
+                is_file_calls: Arc::new(AtomicUsize::new(0)),
+                modified: None,
+                open_files: Arc::new(AtomicUsize::new(0)),
+                seeking_opens: Arc::new(AtomicUsize::new(0)),
+                opens: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    #[rocket::async_trait]
+    impl FileSystem for MockFileSystem {
+        type Read = MockFile;
+
+        async fn is_file<P>(&self, path: P) -> bool
+            where P: AsRef<Path> + Send
+        {
+            self.is_file_calls.fetch_add(1, Ordering::SeqCst);
+            self.inner.is_file(path).await
+        }
+
+        async fn is_dir<P>(&self, path: P) -> bool
+            where P: AsRef<Path> + Send
+        {
+            self.inner.is_dir(path).await
+        }
+
+        async fn last_modified<P>(&self, path: P) -> Result<SystemTime, Box<dyn StdError>>
+            where P: AsRef<Path> + Send
+        {
+            match self.modified {
+                Some(modified) => Ok(modified),
+                None => self.inner.last_modified(path).await,
+            }
+        }
+
+        async fn size<P>(&self, path: P) -> Result<u64, Box<dyn StdError>>
+            where P: AsRef<Path> + Send
+        {
+            self.inner.size(path).await
+        }
+
+        async fn open<P>(
+            &self,
+            path: P,
+            start: Option<u64>,
+        ) -> Result<<Self as FileSystem>::Read, Box<dyn StdError>>
+            where P: AsRef<Path> + Send
+        {
+            self.opens.fetch_add(1, Ordering::SeqCst);
+            if let Some(delay) = self.open_delay {
+                rocket::tokio::time::sleep(delay).await;
+            }
+            if !self.supports_ranges && start.is_some() {
+                return Err(Box::new(Error::new("can't seek")));
+            }
+            if let Some(description) = self.open_error {
+                return Err(Box::new(Error::new(description)));
+            }
+            if start.is_some() {
+                self.seeking_opens.fetch_add(1, Ordering::SeqCst);
+            }
+            let file = self.inner.open(path, start).await?;
+            self.open_files.fetch_add(1, Ordering::SeqCst);
+            Ok(MockFile {
+                file,
+                open_files: self.open_files.clone(),
+                read_delay: self.read_delay,
+                delay: None,
+            })
+        }
+
+        async fn path_valid<P>(&self, path: P) -> bool
+            where P: AsRef<Path> + Send
+        {
+            self.inner.path_valid(path).await
+        }
+
+        async fn entries<P>(&self, path: P) -> Result<Vec<Entry>, Box<dyn StdError>>
+            where P: AsRef<Path> + Send
+        {
+            self.inner.entries(path).await
+        }
+
+        fn supports_ranges(&self) -> bool {
+            self.supports_ranges
+        }
+
+        async fn stat<P>(&self, path: P) -> Result<Option<FileMeta>, Box<dyn StdError>>
+            where P: AsRef<Path> + Send
+        {
+            self.stats.fetch_add(1, Ordering::SeqCst);
+            match (self.inner.stat(path).await?, self.modified) {
+                (Some(FileMeta::File(size, _)), Some(modified)) => {
+                    Ok(Some(FileMeta::File(size, modified)))
+                }
+                (meta, _) => Ok(meta),
+            }
+        }
     }
 }
@@ -0,0 +1,33 @@
+//! Caps how many files are open at once, so busy servers don't run out of file descriptors.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::sync::OwnedSemaphorePermit;
+
+/// Wraps the reader of a served file, so the `max_concurrent_opens` permit taken for it is
+/// only given back along with the file handle.
+pub(crate) struct PermitReader<R> {
+    inner: R,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<R> PermitReader<R> {
+    pub(crate) fn new(inner: R, permit: OwnedSemaphorePermit) -> Self {
+        PermitReader {
+            inner,
+            _permit: permit,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for PermitReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
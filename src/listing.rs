@@ -0,0 +1,124 @@
+//! Renders directory listings, either at once or entry by entry while they're being sent.
+//! Either way, the entries have all been read and filtered beforehand.
+
+use crate::fs::TemplateEntry;
+use crate::DirectoryListingContext;
+use handlebars::{Handlebars, RenderError};
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::vec;
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Registers the listing template along with the header, entry and footer parts it's made of,
+/// and the one of listings as feeds.
+pub(crate) fn listing_templates() -> Handlebars<'static> {
+    let mut hbs = Handlebars::new();
+    for (name, template) in [
+        (
+            "directory_listing_header",
+            include_str!("../templates/directory_listing_header.hbs"),
+        ),
+        (
+            "directory_listing_entry",
+            include_str!("../templates/directory_listing_entry.hbs"),
+        ),
+        (
+            "directory_listing_footer",
+            include_str!("../templates/directory_listing_footer.hbs"),
+        ),
+        (
+            "directory_listing",
+            include_str!("../templates/directory_listing.hbs"),
+        ),
+        (
+            "directory_listing_rss",
+            include_str!("../templates/directory_listing_rss.hbs"),
+        ),
+    ] {
+        hbs.register_template_string(name, template).unwrap();
+    }
+    hbs
+}
+
+/// The context of an entry rendered on its own, which refers to `base` just like it does
+/// within the whole listing.
+#[derive(Serialize)]
+struct EntryContext<'a> {
+    base: &'a str,
+    #[serde(flatten)]
+    entry: &'a TemplateEntry,
+}
+
+/// Reads as the rendered listing, rendering each of the already gathered entries only once
+/// the previous one has been handed out. The header and footer are rendered upfront, so
+/// template errors there still turn into an error response.
+pub(crate) struct ListingStream {
+    hbs: Arc<Handlebars<'static>>,
+    base: String,
+    entries: vec::IntoIter<TemplateEntry>,
+    footer: Option<String>,
+    pending: Vec<u8>,
+    pending_pos: usize,
+}
+
+impl ListingStream {
+    /// Renders `context` with its entries taken out, which are rendered as the stream is read.
+    pub(crate) fn new(
+        hbs: Arc<Handlebars<'static>>,
+        mut context: DirectoryListingContext,
+    ) -> Result<Self, RenderError> {
+        let entries = std::mem::take(&mut context.entries);
+        let header = hbs.render("directory_listing_header", &context)?;
+        let footer = hbs.render("directory_listing_footer", &context)?;
+        Ok(ListingStream {
+            hbs,
+            base: context.base,
+            entries: entries.into_iter(),
+            footer: Some(footer),
+            pending: header.into_bytes(),
+            pending_pos: 0,
+        })
+    }
+}
+
+impl AsyncRead for ListingStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        loop {
+            if this.pending_pos < this.pending.len() {
+                let len = buf.remaining().min(this.pending.len() - this.pending_pos);
+                buf.put_slice(&this.pending[this.pending_pos..this.pending_pos + len]);
+                this.pending_pos += len;
+                return Poll::Ready(Ok(()));
+            }
+
+            let next = match this.entries.next() {
+                Some(entry) => {
+                    let context = EntryContext {
+                        base: &this.base,
+                        entry: &entry,
+                    };
+                    this.hbs
+                        .render("directory_listing_entry", &context)
+                        .map_err(io::Error::other)?
+                }
+                None => match this.footer.take() {
+                    Some(footer) => footer,
+                    None => return Poll::Ready(Ok(())),
+                },
+            };
+            this.pending = next.into_bytes();
+            this.pending_pos = 0;
+        }
+    }
+}
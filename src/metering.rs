@@ -0,0 +1,49 @@
+//! Counts the bytes of response bodies, for bandwidth metering.
+
+use crate::BytesSentCallback;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Wraps the body of a response and adds up the bytes it hands out. The sum is reported when
+/// Rocket is done with the body, so a partial total tells of a client which left early.
+pub(crate) struct CountingReader<R> {
+    inner: R,
+    path: String,
+    sent: u64,
+    callback: Arc<BytesSentCallback>,
+}
+
+impl<R> CountingReader<R> {
+    pub(crate) fn new(inner: R, path: &str, callback: Arc<BytesSentCallback>) -> Self {
+        CountingReader {
+            inner,
+            path: path.to_string(),
+            sent: 0,
+            callback,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CountingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            self.sent += (buf.filled().len() - before) as u64;
+        }
+        poll
+    }
+}
+
+impl<R> Drop for CountingReader<R> {
+    fn drop(&mut self) {
+        (self.callback)(&self.path, self.sent)
+    }
+}
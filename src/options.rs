@@ -1,16 +1,117 @@
+use regex::Regex;
+use rocket::http::Status;
+use rocket::Request;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
 #[derive(Clone)]
 pub struct Options {
     allow_directory_listing: bool,
-    //    directory_listing_default_index: Option<String>,
+    directory_listing_default_index: Option<String>,
     prefix: String,
+    maintenance: Option<MaintenanceConfig>,
+    gzip_only_assets: bool,
+    max_path_depth: Option<usize>,
+    max_path_length: Option<usize>,
+    archive_download: bool,
+    listing_title: Option<String>,
+    default_content_type: String,
+    try_extensions: Vec<String>,
+    slow_file_warning: Option<SlowFileWarning>,
+    cross_origin_isolation: bool,
+    disable_last_modified: bool,
+    small_file_threshold: Option<usize>,
+    charset_map: HashMap<String, String>,
+    hidden_index_files: Vec<String>,
+    server_header: Option<Option<String>>,
+    disable_directory_redirect: bool,
+    immutable_prefix: Option<String>,
+    max_listing_entries: Option<usize>,
+    transform: Option<Arc<FileTransform>>,
+    canonical_host: Option<String>,
+    not_found_response: Option<NotFoundResponse>,
+    empty_directory_not_found: bool,
+    request_filter: Option<Arc<RequestFilter>>,
+    compress_min_size: u64,
+    max_concurrent_opens: Option<usize>,
+    no_store_pattern: Option<Regex>,
+    hide_index_in_url: bool,
+    plus_as_space: bool,
+    no_range_pattern: Option<Regex>,
+    precompressed_variants: bool,
+    content_language_from_filename: bool,
+    language_negotiation: bool,
+    stream_directory_listing: bool,
+    bytes_sent_callback: Option<Arc<BytesSentCallback>>,
+    query_in_etag: bool,
+    disposition_by_mime: HashMap<String, Disposition>,
+    listing_disabled_status: Option<Status>,
+    html_banner: Option<String>,
+    throttle_bytes_per_sec: Option<u64>,
+    listing_dirs: Vec<String>,
+    source_map_origins: Vec<String>,
+    etag_strength: Option<EtagStrength>,
+    serve_hidden_paths: bool,
+    list_hidden_paths: bool,
+    allow_checksum_query: bool,
+    normalize_backslashes: bool,
+    listing_feed: bool,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Options {
             allow_directory_listing: false,
-            //            directory_listing_default_index: None,
+            directory_listing_default_index: None,
             prefix: "/".to_string(),
+            maintenance: None,
+            gzip_only_assets: false,
+            max_path_depth: None,
+            max_path_length: None,
+            archive_download: false,
+            listing_title: None,
+            default_content_type: "application/octet-stream".to_string(),
+            try_extensions: Vec::new(),
+            slow_file_warning: None,
+            cross_origin_isolation: false,
+            disable_last_modified: false,
+            small_file_threshold: None,
+            charset_map: HashMap::new(),
+            hidden_index_files: Vec::new(),
+            server_header: None,
+            disable_directory_redirect: false,
+            immutable_prefix: None,
+            max_listing_entries: None,
+            transform: None,
+            canonical_host: None,
+            not_found_response: None,
+            empty_directory_not_found: false,
+            request_filter: None,
+            compress_min_size: 1024,
+            max_concurrent_opens: None,
+            no_store_pattern: None,
+            hide_index_in_url: false,
+            plus_as_space: false,
+            no_range_pattern: None,
+            precompressed_variants: false,
+            content_language_from_filename: false,
+            language_negotiation: false,
+            stream_directory_listing: false,
+            bytes_sent_callback: None,
+            query_in_etag: false,
+            disposition_by_mime: HashMap::new(),
+            listing_disabled_status: None,
+            html_banner: None,
+            throttle_bytes_per_sec: None,
+            listing_dirs: Vec::new(),
+            source_map_origins: Vec::new(),
+            etag_strength: None,
+            serve_hidden_paths: true,
+            list_hidden_paths: true,
+            allow_checksum_query: false,
+            normalize_backslashes: false,
+            listing_feed: false,
         }
     }
 }
@@ -20,13 +121,238 @@ impl Options {
         self.allow_directory_listing
     }
 
-    //    pub fn directory_listing_default_index(&self) -> Option<&String> {
-    //        self.directory_listing_default_index.as_ref()
-    //    }
+    pub fn directory_listing_default_index(&self) -> Option<&String> {
+        self.directory_listing_default_index.as_ref()
+    }
 
     pub fn prefix(&self) -> &str {
         &self.prefix
     }
+
+    pub fn maintenance(&self) -> Option<&MaintenanceConfig> {
+        self.maintenance.as_ref()
+    }
+
+    pub fn gzip_only_assets(&self) -> bool {
+        self.gzip_only_assets
+    }
+
+    pub fn max_path_depth(&self) -> Option<usize> {
+        self.max_path_depth
+    }
+
+    pub fn max_path_length(&self) -> Option<usize> {
+        self.max_path_length
+    }
+
+    pub fn archive_download(&self) -> bool {
+        self.archive_download
+    }
+
+    pub fn listing_title(&self) -> Option<&String> {
+        self.listing_title.as_ref()
+    }
+
+    pub fn default_content_type(&self) -> &str {
+        &self.default_content_type
+    }
+
+    pub fn try_extensions(&self) -> &[String] {
+        &self.try_extensions
+    }
+
+    pub fn slow_file_warning(&self) -> Option<&SlowFileWarning> {
+        self.slow_file_warning.as_ref()
+    }
+
+    pub fn cross_origin_isolation(&self) -> bool {
+        self.cross_origin_isolation
+    }
+
+    pub fn disable_last_modified(&self) -> bool {
+        self.disable_last_modified
+    }
+
+    pub fn small_file_threshold(&self) -> Option<usize> {
+        self.small_file_threshold
+    }
+
+    pub fn charset_map(&self) -> &HashMap<String, String> {
+        &self.charset_map
+    }
+
+    /// Looks up the charset for a mime type, preferring an exact match over its `type/*` family.
+    pub fn charset_for(&self, mime: &str) -> Option<&str> {
+        let family = mime.split('/').next().unwrap_or(mime);
+        self.charset_map
+            .get(mime)
+            .or_else(|| self.charset_map.get(&format!("{}/*", family)))
+            .map(String::as_str)
+    }
+
+    pub fn hidden_index_files(&self) -> &[String] {
+        &self.hidden_index_files
+    }
+
+    pub fn server_header(&self) -> Option<Option<&String>> {
+        self.server_header.as_ref().map(Option::as_ref)
+    }
+
+    pub fn disable_directory_redirect(&self) -> bool {
+        self.disable_directory_redirect
+    }
+
+    pub fn immutable_prefix(&self) -> Option<&String> {
+        self.immutable_prefix.as_ref()
+    }
+
+    pub fn max_listing_entries(&self) -> Option<usize> {
+        self.max_listing_entries
+    }
+
+    pub fn transform(&self) -> Option<&FileTransform> {
+        self.transform.as_deref()
+    }
+
+    pub fn canonical_host(&self) -> Option<&String> {
+        self.canonical_host.as_ref()
+    }
+
+    pub fn not_found_response(&self) -> Option<&NotFoundResponse> {
+        self.not_found_response.as_ref()
+    }
+
+    pub fn empty_directory_not_found(&self) -> bool {
+        self.empty_directory_not_found
+    }
+
+    pub fn request_filter(&self) -> Option<&RequestFilter> {
+        self.request_filter.as_deref()
+    }
+
+    pub fn compress_min_size(&self) -> u64 {
+        self.compress_min_size
+    }
+
+    pub fn max_concurrent_opens(&self) -> Option<usize> {
+        self.max_concurrent_opens
+    }
+
+    pub fn no_store_pattern(&self) -> Option<&Regex> {
+        self.no_store_pattern.as_ref()
+    }
+
+    pub fn hide_index_in_url(&self) -> bool {
+        self.hide_index_in_url
+    }
+
+    pub fn plus_as_space(&self) -> bool {
+        self.plus_as_space
+    }
+
+    pub fn no_range_pattern(&self) -> Option<&Regex> {
+        self.no_range_pattern.as_ref()
+    }
+
+    pub fn precompressed_variants(&self) -> bool {
+        self.precompressed_variants
+    }
+
+    pub fn content_language_from_filename(&self) -> bool {
+        self.content_language_from_filename
+    }
+
+    pub fn language_negotiation(&self) -> bool {
+        self.language_negotiation
+    }
+
+    pub fn stream_directory_listing(&self) -> bool {
+        self.stream_directory_listing
+    }
+
+    pub fn bytes_sent_callback(&self) -> Option<&Arc<BytesSentCallback>> {
+        self.bytes_sent_callback.as_ref()
+    }
+
+    pub fn query_in_etag(&self) -> bool {
+        self.query_in_etag
+    }
+
+    pub fn disposition_by_mime(&self) -> &HashMap<String, Disposition> {
+        &self.disposition_by_mime
+    }
+
+    /// Looks up the disposition for a mime type, preferring an exact match over its `type/*`
+    /// family.
+    pub fn disposition_for(&self, mime: &str) -> Option<Disposition> {
+        let family = mime.split('/').next().unwrap_or(mime);
+        self.disposition_by_mime
+            .get(mime)
+            .or_else(|| self.disposition_by_mime.get(&format!("{}/*", family)))
+            .copied()
+    }
+
+    pub fn listing_disabled_status(&self) -> Option<Status> {
+        self.listing_disabled_status
+    }
+
+    pub fn html_banner(&self) -> Option<&str> {
+        self.html_banner.as_deref()
+    }
+
+    pub fn throttle_bytes_per_sec(&self) -> Option<u64> {
+        self.throttle_bytes_per_sec
+    }
+
+    pub fn listing_dirs(&self) -> &[String] {
+        &self.listing_dirs
+    }
+
+    pub fn source_map_origins(&self) -> &[String] {
+        &self.source_map_origins
+    }
+
+    pub fn etag_strength(&self) -> Option<EtagStrength> {
+        self.etag_strength
+    }
+
+    pub fn serve_hidden_paths(&self) -> bool {
+        self.serve_hidden_paths
+    }
+
+    pub fn list_hidden_paths(&self) -> bool {
+        self.list_hidden_paths
+    }
+
+    pub fn allow_checksum_query(&self) -> bool {
+        self.allow_checksum_query
+    }
+
+    pub fn normalize_backslashes(&self) -> bool {
+        self.normalize_backslashes
+    }
+
+    pub fn listing_feed(&self) -> bool {
+        self.listing_feed
+    }
+
+    /// Whether the directory at `path`, relative to the prefix, may be listed: all of them
+    /// with `allow_directory_listing`, otherwise the ones at or below one of `listing_dirs`.
+    /// Hidden directories never are without `list_hidden_paths`.
+    pub fn listing_allowed(&self, path: &str) -> bool {
+        if !self.list_hidden_paths && crate::is_hidden(path) {
+            return false;
+        }
+        let path = path.trim_matches('/');
+        self.allow_directory_listing
+            || self.listing_dirs.iter().any(|dir| {
+                let dir = dir.trim_matches('/');
+                match path.strip_prefix(dir) {
+                    Some(rest) => dir.is_empty() || rest.is_empty() || rest.starts_with('/'),
+                    None => false,
+                }
+            })
+    }
 }
 
 #[derive(Clone)]
@@ -46,22 +372,588 @@ impl OptionsBuilder {
         self
     }
 
-    //    pub fn directory_listing_default_index(mut self, default_index: &str) -> Self {
-    //        self.options.directory_listing_default_index = Some(default_index.to_string());
-    //        self
-    //    }
+    /// Serves the file named `default_index` for requests to a directory containing one, with
+    /// the content type of the index file, e.g. `README.md` as `text/markdown`.
+    pub fn directory_listing_default_index(mut self, default_index: &str) -> Self {
+        self.options.directory_listing_default_index = Some(default_index.to_string());
+        self
+    }
 
     pub fn prefix(mut self, prefix: &str) -> Self {
         self.options.prefix = prefix.to_string();
         self
     }
+
+    /// Answers every matching request with a `503 Service Unavailable` while set.
+    pub fn maintenance(mut self, maintenance: Option<MaintenanceConfig>) -> Self {
+        self.options.maintenance = maintenance;
+        self
+    }
+
+    /// Serves `app.js` from `app.js.gz` when only the compressed file exists. Clients not
+    /// accepting gzip get it decompressed on the fly (requires the `content_encoding` feature).
+    pub fn gzip_only_assets(mut self, enabled: bool) -> Self {
+        self.options.gzip_only_assets = enabled;
+        self
+    }
+
+    /// Rejects requests with more path segments (below the prefix) than `depth` as not found.
+    pub fn max_path_depth(mut self, depth: usize) -> Self {
+        self.options.max_path_depth = Some(depth);
+        self
+    }
+
+    /// Rejects requests whose path (below the prefix, as sent) is longer than `length` bytes
+    /// with 414 URI Too Long.
+    pub fn max_path_length(mut self, length: usize) -> Self {
+        self.options.max_path_length = Some(length);
+        self
+    }
+
+    /// Lets directories be downloaded as an archive of all their files with
+    /// `?download=zip`, `?download=tar` or (with `content_encoding`) `?download=tar.gz`.
+    pub fn archive_download(mut self, allow: bool) -> Self {
+        self.options.archive_download = allow;
+        self
+    }
+
+    /// Sets a site name shown on directory listings and their breadcrumb root.
+    pub fn listing_title(mut self, title: &str) -> Self {
+        self.options.listing_title = Some(title.to_string());
+        self
+    }
+
+    /// Sets the `Content-Type` of files whose type can't be guessed from their extension.
+    /// Defaults to `application/octet-stream`.
+    pub fn default_content_type(mut self, content_type: &str) -> Self {
+        self.options.default_content_type = content_type.to_string();
+        self
+    }
+
+    /// Extensions to try in order when the requested path isn't a file, so e.g. `/about`
+    /// serves `about.html` with `vec![".html".to_string()]`.
+    pub fn try_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.options.try_extensions = extensions;
+        self
+    }
+
+    /// Calls `callback` with the path and duration of every file response which took longer
+    /// than `threshold`. That's measured from when the server picks up the request, after
+    /// Rocket routed it, so looking up and opening the file count, until the body has been
+    /// read out to the client.
+    pub fn slow_file_warning<F>(mut self, threshold: Duration, callback: F) -> Self
+    where
+        F: Fn(&str, Duration) + Send + Sync + 'static,
+    {
+        self.options.slow_file_warning = Some(SlowFileWarning {
+            threshold,
+            callback: Arc::new(callback),
+        });
+        self
+    }
+
+    /// Sends `Cross-Origin-Opener-Policy: same-origin` and
+    /// `Cross-Origin-Embedder-Policy: require-corp`, which browsers require before allowing
+    /// e.g. `SharedArrayBuffer` for WebAssembly threads.
+    pub fn cross_origin_isolation(mut self, enabled: bool) -> Self {
+        self.options.cross_origin_isolation = enabled;
+        self
+    }
+
+    /// Omits the `Last-Modified` header and ignores `If-Modified-Since`, e.g. for embedded
+    /// packages whose modification times are just the time they were built.
+    pub fn disable_last_modified(mut self, disable: bool) -> Self {
+        self.options.disable_last_modified = disable;
+        self
+    }
+
+    /// Reads bodies smaller than `threshold` bytes into memory, so they're sent with an exact
+    /// `Content-Length` instead of being streamed.
+    pub fn small_file_threshold(mut self, threshold: usize) -> Self {
+        self.options.small_file_threshold = Some(threshold);
+        self
+    }
+
+    /// Maps mime types like `text/csv`, or whole families like `text/*`, to the charset
+    /// appended to their `Content-Type`.
+    pub fn charset_map(mut self, map: HashMap<String, String>) -> Self {
+        self.options.charset_map = map;
+        self
+    }
+
+    /// Leaves files with these names, e.g. `index.html`, out of directory listings.
+    ///
+    /// A directory with the `directory_listing_default_index` in it is never listed, its
+    /// index is served instead, so a listing can't show that file anyway. This is for the
+    /// other index names found in served directories, like `index.htm` or `default.html`, or
+    /// for listing directories when no default index is configured.
+    pub fn hide_index_files(mut self, names: Vec<String>) -> Self {
+        self.options.hidden_index_files = names;
+        self
+    }
+
+    /// Replaces Rocket's `Server` header on served responses with `server`, or removes it
+    /// entirely with `None`.
+    pub fn server_header(mut self, server: Option<String>) -> Self {
+        self.options.server_header = Some(server);
+        self
+    }
+
+    /// Renders directory listings at `/dir` as well instead of redirecting to `/dir/`, with
+    /// absolute links so they work from either form.
+    pub fn disable_directory_redirect(mut self, disable: bool) -> Self {
+        self.options.disable_directory_redirect = disable;
+        self
+    }
+
+    /// Files whose request path starts with `prefix`, e.g. `/assets/static/`, are cached for
+    /// a year with `Cache-Control: public, max-age=31536000, immutable`.
+    pub fn immutable_prefix(mut self, prefix: &str) -> Self {
+        self.options.immutable_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Only lists the first `max` entries of a directory and notes that the listing was
+    /// truncated, so accidentally huge directories don't take the server down.
+    pub fn max_listing_entries(mut self, max: usize) -> Self {
+        self.options.max_listing_entries = Some(max);
+        self
+    }
+
+    /// Passes the path and contents of every served file through `transform` and sends its
+    /// result instead, e.g. to inject a `<base>` tag into HTML. Files are read into memory for
+    /// this and sent without ranges or compression; gzip only assets are left alone. HEAD
+    /// requests don't read them, so they're answered without a `Content-Length`.
+    pub fn transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(&str, Vec<u8>) -> Vec<u8> + Send + Sync + 'static,
+    {
+        self.options.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Permanently redirects requests for any other `Host`, e.g. `www.example.com`, to the
+    /// same path and query on `host`.
+    pub fn canonical_host(mut self, host: Option<String>) -> Self {
+        self.options.canonical_host = host;
+        self
+    }
+
+    /// Answers requests for missing files with `status`, `body` and `content_type` instead of
+    /// Rocket's 404 page, e.g. with a JSON error for API consumers.
+    pub fn not_found_response(mut self, status: Status, body: &str, content_type: &str) -> Self {
+        self.options.not_found_response = Some(NotFoundResponse {
+            status,
+            body: body.to_string(),
+            content_type: content_type.to_string(),
+        });
+        self
+    }
+
+    /// Answers requests for directories without any entries with a not found instead of an
+    /// empty listing. Entries which are never listed, like hidden index files, don't count.
+    pub fn empty_directory_not_found(mut self, enabled: bool) -> Self {
+        self.options.empty_directory_not_found = enabled;
+        self
+    }
+
+    /// Asks `filter` before serving anything, requests it returns false for are answered
+    /// with a 403, e.g. to check for a signed token.
+    pub fn request_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Request<'_>) -> bool + Send + Sync + 'static,
+    {
+        self.options.request_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Sends files smaller than `size` bytes uncompressed even if the client accepts an
+    /// encoding, since the encoding overhead would outweigh the savings. Defaults to 1KiB.
+    pub fn compress_min_size(mut self, size: u64) -> Self {
+        self.options.compress_min_size = size;
+        self
+    }
+
+    /// Keeps at most `max` files open at once, from opening until their body has been sent.
+    /// Requests for files beyond that are answered with a `503 Service Unavailable`, while
+    /// archive downloads, which open one file at a time, wait for their turn.
+    pub fn max_concurrent_opens(mut self, max: usize) -> Self {
+        self.options.max_concurrent_opens = Some(max);
+        self
+    }
+
+    /// Sends files whose request path matches `pattern`, e.g. `^/private/`, with
+    /// `Cache-Control: no-store` and without `Last-Modified`, so they're never cached.
+    pub fn no_store_pattern(mut self, pattern: Regex) -> Self {
+        self.options.no_store_pattern = Some(pattern);
+        self
+    }
+
+    /// Permanently redirects requests naming the `directory_listing_default_index` file, e.g.
+    /// `/docs/index.html`, to its directory `/docs/`, so every page has a single URL.
+    pub fn hide_index_in_url(mut self, hide: bool) -> Self {
+        self.options.hide_index_in_url = hide;
+        self
+    }
+
+    /// Decodes `+` in request paths as a space, for clients which encode paths like form
+    /// values. By default a `+` is just a `+`, and spaces have to be sent as `%20`.
+    pub fn plus_as_space(mut self, enabled: bool) -> Self {
+        self.options.plus_as_space = enabled;
+        self
+    }
+
+    /// Ignores `Range` headers for files whose request path matches `pattern` and sends them
+    /// with `Accept-Ranges: none`, e.g. for files whose bytes change between requests.
+    pub fn no_range_pattern(mut self, pattern: Regex) -> Self {
+        self.options.no_range_pattern = Some(pattern);
+        self
+    }
+
+    /// Serves `app.js` from a precompressed `app.js.br` or `app.js.gz` next to it to clients
+    /// accepting that encoding. If both exist, the client's quality values decide, with
+    /// Brotli preferred on a tie. Ranged requests are answered from `app.js` itself, so
+    /// `Content-Range` counts uncompressed bytes.
+    pub fn precompressed_variants(mut self, enabled: bool) -> Self {
+        self.options.precompressed_variants = enabled;
+        self
+    }
+
+    /// Sends a `Content-Language` header for files named like `page.fr.html` or
+    /// `index.pt-BR.html`, taken from the segment before the extension. Only two letter
+    /// language codes count, so `jquery.min.js` isn't mistaken for one.
+    pub fn content_language_from_filename(mut self, enabled: bool) -> Self {
+        self.options.content_language_from_filename = enabled;
+        self
+    }
+
+    /// Serves language variants like `page.fr.html` in place of `page.html` to clients
+    /// preferring that language by their `Accept-Language` header, with the matching
+    /// `Content-Language`. Language codes follow `content_language_from_filename`.
+    pub fn language_negotiation(mut self, enabled: bool) -> Self {
+        self.options.language_negotiation = enabled;
+        self
+    }
+
+    /// Renders directory listings entry by entry while they're sent, instead of into one
+    /// string first, so huge listings are never in memory as a whole. They're sent without a
+    /// `Content-Length` then.
+    ///
+    /// This doesn't stream the directory itself: its entries are still all read and
+    /// filtered before the header goes out, so the first byte waits on those.
+    pub fn stream_directory_listing(mut self, enabled: bool) -> Self {
+        self.options.stream_directory_listing = enabled;
+        self
+    }
+
+    /// Calls `callback` with the request path and the number of body bytes sent for every
+    /// response of the server, once it's done. Compressed bodies count as sent, ranges only
+    /// with the bytes of the range, and clients going away early only with what they got.
+    pub fn on_bytes_sent<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&str, u64) + Send + Sync + 'static,
+    {
+        self.options.bytes_sent_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Makes the query part of the ETag, so `/app.js?v=abc` and `/app.js?v=def` are
+    /// different resources to caches. The query never affects which file is served.
+    pub fn query_in_etag(mut self, enabled: bool) -> Self {
+        self.options.query_in_etag = enabled;
+        self
+    }
+
+    /// Maps mime types like `application/pdf`, or whole families like `image/*`, to whether
+    /// browsers should show files of them or download them, sent as `Content-Disposition`.
+    pub fn disposition_by_mime(mut self, map: HashMap<String, Disposition>) -> Self {
+        self.options.disposition_by_mime = map;
+        self
+    }
+
+    /// Answers requests for directories with `status`, e.g. a 403, while listing is disabled,
+    /// so they can be told apart from missing files. Without it they're not found as well.
+    pub fn listing_disabled_status(mut self, status: Status) -> Self {
+        self.options.listing_disabled_status = Some(status);
+        self
+    }
+
+    /// Injects `banner` before the `</body>` of served HTML files, e.g. to announce
+    /// maintenance. Like with `transform`, these files are read into memory and sent without
+    /// compression; requests for ranges of them get the file as it is. Precompressed variants
+    /// of them are skipped, but gzip only assets are sent without the banner. The banner is
+    /// part of the ETag, so cached pages are sent again once it changes.
+    pub fn html_banner(mut self, banner: Option<String>) -> Self {
+        self.options.html_banner = banner;
+        self
+    }
+
+    /// Sends response bodies at no more than `bytes_per_sec`, e.g. to test how clients retry
+    /// or resume slow downloads. Not meant for production, where a proxy does this better.
+    pub fn throttle_bytes_per_sec(mut self, bytes_per_sec: Option<u64>) -> Self {
+        // No bandwidth at all would never finish a response
+        self.options.throttle_bytes_per_sec = bytes_per_sec.filter(|bytes| *bytes > 0);
+        self
+    }
+
+    /// Lists only the directories at or below `dirs`, like `/downloads/`, just as
+    /// `allow_directory_listing` lists all of them. Others are answered as if listing were
+    /// disabled.
+    pub fn listing_dirs(mut self, dirs: Vec<String>) -> Self {
+        self.options.listing_dirs = dirs;
+        self
+    }
+
+    /// Serves `.map` files only to requests whose `Origin`, or the origin of their `Referer`,
+    /// is one of `origins`, like `https://internal.example.com`. Everyone else gets a not
+    /// found, so source maps can be used for debugging without being public. This covers
+    /// `.map` in any case, compressed copies like `.map.gz` and archive downloads.
+    pub fn source_map_origins(mut self, origins: Vec<String>) -> Self {
+        self.options.source_map_origins = origins;
+        self
+    }
+
+    /// Sends and compares ETags as strong or weak validators only. By default they're strong
+    /// for responses with the file's bytes and weak for compressed or transformed ones, and
+    /// `If-None-Match` compares them weakly, as RFC 7232 asks for.
+    pub fn etag_strength(mut self, strength: EtagStrength) -> Self {
+        self.options.etag_strength = Some(strength);
+        self
+    }
+
+    /// Serves files whose path has a part starting with a dot, like `/.env` or
+    /// `/.well-known/security.txt`. Enabled by default, disabled they're not found.
+    pub fn serve_hidden_paths(mut self, enabled: bool) -> Self {
+        self.options.serve_hidden_paths = enabled;
+        self
+    }
+
+    /// Lists hidden files and directories, like `.well-known`, and hidden directories
+    /// themselves. Enabled by default; disabled, they can still be served by their path.
+    /// Hidden paths which aren't served aren't listed either.
+    pub fn list_hidden_paths(mut self, enabled: bool) -> Self {
+        self.options.list_hidden_paths = enabled;
+        self
+    }
+
+    /// Answers `?checksum=sha256` or `?checksum=crc32` on a file with its checksum in hex as
+    /// `text/plain`, instead of the file. Other algorithms are a bad request.
+    pub fn allow_checksum_query(mut self, enabled: bool) -> Self {
+        self.options.allow_checksum_query = enabled;
+        self
+    }
+
+    /// Takes backslashes in request paths as slashes, for clients sending Windows paths like
+    /// `/docs\index.html`. Paths with a `..` are forbidden either way.
+    pub fn normalize_backslashes(mut self, enabled: bool) -> Self {
+        self.options.normalize_backslashes = enabled;
+        self
+    }
+
+    /// Serves listings as RSS feeds of the directory's files, newest first, if asked for with
+    /// `?format=rss`, e.g. for subscribing to a directory of releases. Links point to the
+    /// `canonical_host` if there is one, otherwise to the host the client asked.
+    pub fn listing_feed(mut self, enabled: bool) -> Self {
+        self.options.listing_feed = enabled;
+        self
+    }
+}
+
+impl Default for OptionsBuilder {
+    fn default() -> Self {
+        OptionsBuilder::new()
+    }
 }
 
-impl Into<Options> for OptionsBuilder {
-    fn into(mut self) -> Options {
-        if !self.options.prefix.ends_with('/') {
-            self.options.prefix.push('/')
+impl From<OptionsBuilder> for Options {
+    fn from(mut builder: OptionsBuilder) -> Options {
+        if !builder.options.prefix.ends_with('/') {
+            builder.options.prefix.push('/')
         }
-        self.options
+        builder.options
     }
 }
+
+/// Describes the response sent while the server is in maintenance mode.
+#[derive(Clone)]
+pub struct MaintenanceConfig {
+    retry_after: u64,
+    page: Option<String>,
+}
+
+impl MaintenanceConfig {
+    /// `retry_after` is sent as the `Retry-After` header, in seconds.
+    pub fn new(retry_after: u64) -> Self {
+        MaintenanceConfig {
+            retry_after,
+            page: None,
+        }
+    }
+
+    /// Sets an HTML page to send as the body of maintenance responses.
+    pub fn page(mut self, page: &str) -> Self {
+        self.page = Some(page.to_string());
+        self
+    }
+
+    pub fn retry_after(&self) -> u64 {
+        self.retry_after
+    }
+
+    pub fn page_content(&self) -> Option<&String> {
+        self.page.as_ref()
+    }
+}
+
+/// Describes the response sent for missing files.
+#[derive(Clone)]
+pub struct NotFoundResponse {
+    status: Status,
+    body: String,
+    content_type: String,
+}
+
+impl NotFoundResponse {
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+}
+
+type SlowFileCallback = dyn Fn(&str, Duration) + Send + Sync;
+
+/// Decides whether a request may be served, see `OptionsBuilder::request_filter`.
+pub type RequestFilter = dyn Fn(&Request<'_>) -> bool + Send + Sync;
+
+/// Reports the body bytes sent for a request path, see `OptionsBuilder::on_bytes_sent`.
+pub type BytesSentCallback = dyn Fn(&str, u64) + Send + Sync;
+
+/// Rewrites the contents of the file at the given path, see `OptionsBuilder::transform`.
+pub type FileTransform = dyn Fn(&str, Vec<u8>) -> Vec<u8> + Send + Sync;
+
+/// Whether a file is meant to be shown by the browser or downloaded, see
+/// `OptionsBuilder::disposition_by_mime`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Disposition {
+    Inline,
+    Attachment,
+}
+
+impl Disposition {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Disposition::Inline => "inline",
+            Disposition::Attachment => "attachment",
+        }
+    }
+}
+
+/// How ETags are sent and compared, see `OptionsBuilder::etag_strength`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EtagStrength {
+    /// Sent as `"abc"` where the bytes are the file's, and only matched by an identical
+    /// strong tag, not by `W/"abc"`. Responses whose bytes differ still get a weak one.
+    Strong,
+    /// Always sent as `W/"abc"`, and matched with or without the `W/`.
+    Weak,
+}
+
+impl EtagStrength {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EtagStrength::Strong => "strong",
+            EtagStrength::Weak => "weak",
+        }
+    }
+}
+
+/// Reports file responses which took longer than a threshold.
+#[derive(Clone)]
+pub struct SlowFileWarning {
+    threshold: Duration,
+    callback: Arc<SlowFileCallback>,
+}
+
+impl SlowFileWarning {
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    pub fn report(&self, path: &str, elapsed: Duration) {
+        (self.callback)(path, elapsed)
+    }
+}
+
+/// A snapshot of what a `StaticFileServer` has been configured with, see
+/// `StaticFileServer::effective_options`. Callbacks are only noted as being set or not.
+///
+/// It covers every option, so it gains fields along with `OptionsBuilder`.
+#[derive(Serialize, Clone, Debug)]
+#[non_exhaustive]
+pub struct EffectiveOptions {
+    pub prefix: String,
+    pub canonical_host: Option<String>,
+    pub allow_directory_listing: bool,
+    pub listing_dirs: Vec<String>,
+    /// The status code directories are answered with while listing is disabled, if not 404.
+    pub listing_disabled_status: Option<u16>,
+    pub listing_title: Option<String>,
+    pub disable_directory_redirect: bool,
+    pub empty_directory_not_found: bool,
+    pub directory_listing_default_index: Option<String>,
+    pub hide_index_in_url: bool,
+    pub hidden_index_files: Vec<String>,
+    pub plus_as_space: bool,
+    pub try_extensions: Vec<String>,
+    pub default_content_type: String,
+    /// The status code of the custom not found response, if there is one.
+    pub not_found_response: Option<u16>,
+    /// Left out while Rocket's own `Server` header is sent, `null` while it's removed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_header: Option<Option<String>>,
+    pub cross_origin_isolation: bool,
+    /// Content types by extension which take precedence over the guessed ones.
+    pub mime_overrides: BTreeMap<String, String>,
+    pub charset_map: BTreeMap<String, String>,
+    pub disposition_by_mime: BTreeMap<String, String>,
+    pub immutable_prefix: Option<String>,
+    pub no_store_pattern: Option<String>,
+    pub no_range_pattern: Option<String>,
+    pub source_map_origins: Vec<String>,
+    pub disable_last_modified: bool,
+    pub supports_ranges: bool,
+    pub gzip_only_assets: bool,
+    pub precompressed_variants: bool,
+    pub content_language_from_filename: bool,
+    pub language_negotiation: bool,
+    pub stream_directory_listing: bool,
+    pub compress_min_size: u64,
+    pub small_file_threshold: Option<usize>,
+    pub archive_download: bool,
+    pub max_path_depth: Option<usize>,
+    pub max_path_length: Option<usize>,
+    pub max_listing_entries: Option<usize>,
+    pub max_concurrent_opens: Option<usize>,
+    pub maintenance: bool,
+    /// How long reading a file may take before the slow file callback is called.
+    pub slow_file_warning: Option<Duration>,
+    pub transform: bool,
+    pub html_banner: Option<String>,
+    pub throttle_bytes_per_sec: Option<u64>,
+    pub request_filter: bool,
+    pub bytes_sent_callback: bool,
+    pub query_in_etag: bool,
+    pub etag_strength: Option<String>,
+    pub serve_hidden_paths: bool,
+    pub list_hidden_paths: bool,
+    pub allow_checksum_query: bool,
+    pub normalize_backslashes: bool,
+    pub listing_feed: bool,
+}
@@ -0,0 +1,198 @@
+//! Parses `Range` headers like `bytes=0-1023`, `bytes=500-`, `bytes=-500` or lists of them.
+
+use crate::Error;
+use std::error::Error as StdError;
+use std::str::FromStr;
+
+/// A single range of a `Range` header, as the client asked for it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum ByteRange {
+    /// `start-end`, both inclusive.
+    FromTo(u64, u64),
+    /// `start-`, up to the end of the file.
+    From(u64),
+    /// `-len`, the last `len` bytes of the file.
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// Resolves the range against a file of `size` bytes into the first and last byte to send,
+    /// cutting it to the end of the file. Returns `None` if no byte of the file is in range.
+    pub(crate) fn resolve(self, size: u64) -> Option<(u64, u64)> {
+        match self {
+            ByteRange::FromTo(start, end) if start < size => Some((start, end.min(size - 1))),
+            ByteRange::From(start) if start < size => Some((start, size - 1)),
+            ByteRange::Suffix(len) if len > 0 && size > 0 => Some((size - len.min(size), size - 1)),
+            _ => None,
+        }
+    }
+}
+
+/// Represents a `Range` header in `bytes`, the only range unit we support.
+///
+/// Implements FromStr for convenience.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Range {
+    pub(crate) ranges: Vec<ByteRange>,
+}
+
+impl Range {
+    /// Returns the range of a header asking for exactly one. Multipart responses aren't
+    /// supported, so several ranges give `None`.
+    pub(crate) fn single(&self) -> Option<ByteRange> {
+        match self.ranges.as_slice() {
+            [range] => Some(*range),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Range {
+    type Err = Box<dyn StdError + Send + Sync + 'static>;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        let (unit, ranges) = s
+            .split_once('=')
+            .ok_or_else(|| Error::new("invalid range header"))?;
+        let unit = unit.trim();
+        if unit.is_empty() {
+            return Err(Box::new(Error::new("range header without a unit")));
+        }
+        // Servers have to ignore units they don't support, which the caller does on errors
+        if !unit.eq_ignore_ascii_case("bytes") {
+            return Err(Box::new(Error::new("unsupported range unit")));
+        }
+
+        // Lists may contain empty elements, which don't count
+        let ranges = ranges
+            .split(',')
+            .map(str::trim)
+            .filter(|range| !range.is_empty())
+            .map(parse_byte_range)
+            .collect::<Result<Vec<_>, _>>()?;
+        if ranges.is_empty() {
+            return Err(Box::new(Error::new("range header without ranges")));
+        }
+
+        Ok(Range { ranges })
+    }
+}
+
+fn parse_byte_range(s: &str) -> Result<ByteRange, Box<dyn StdError + Send + Sync + 'static>> {
+    let (start, end) = s
+        .split_once('-')
+        .ok_or_else(|| Error::new("invalid range"))?;
+    let (start, end) = (start.trim(), end.trim());
+
+    match (start.is_empty(), end.is_empty()) {
+        (true, true) => Err(Box::new(Error::new("range without start and end"))),
+        (true, false) => Ok(ByteRange::Suffix(parse_position(end)?)),
+        (false, true) => Ok(ByteRange::From(parse_position(start)?)),
+        (false, false) => {
+            let (start, end) = (parse_position(start)?, parse_position(end)?);
+            if end < start {
+                return Err(Box::new(Error::new("range ends before it starts")));
+            }
+            Ok(ByteRange::FromTo(start, end))
+        }
+    }
+}
+
+/// Parses a byte position, which unlike `u64::from_str` doesn't allow a sign.
+fn parse_position(s: &str) -> Result<u64, Box<dyn StdError + Send + Sync + 'static>> {
+    if !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Box::new(Error::new("invalid range position")));
+    }
+    Ok(s.parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Range {
+        s.parse().expect("unable to parse Range header")
+    }
+
+    #[test]
+    fn test_closed_range() {
+        assert_eq!(parse("bytes=0-1023").ranges, vec![ByteRange::FromTo(0, 1023)]);
+
+        assert_eq!(parse("bytes=5-5").ranges, vec![ByteRange::FromTo(5, 5)]);
+        assert!("bytes=10-5".parse::<Range>().is_err());
+    }
+
+    #[test]
+    fn test_open_ended_range() {
+        assert_eq!(parse("bytes=500-").ranges, vec![ByteRange::From(500)]);
+    }
+
+    #[test]
+    fn test_suffix_range() {
+        assert_eq!(parse("bytes=-500").ranges, vec![ByteRange::Suffix(500)]);
+    }
+
+    #[test]
+    fn test_multiple_ranges() {
+        assert_eq!(
+            parse("bytes=0-9,20-,-5").ranges,
+            vec![
+                ByteRange::FromTo(0, 9),
+                ByteRange::From(20),
+                ByteRange::Suffix(5)
+            ]
+        );
+        assert_eq!(parse("bytes=0-9,,").ranges, vec![ByteRange::FromTo(0, 9)]);
+    }
+
+    #[test]
+    fn test_whitespace() {
+        assert_eq!(
+            parse(" bytes = 0 - 9 , 20- ").ranges,
+            vec![ByteRange::FromTo(0, 9), ByteRange::From(20)]
+        );
+    }
+
+    #[test]
+    fn test_other_units() {
+        assert_eq!(parse("Bytes=0-4").ranges, vec![ByteRange::FromTo(0, 4)]);
+        for header in &["items=0-4", "bytesx=0-4", "none=0-4"] {
+            assert!(header.parse::<Range>().is_err(), "{:?} was accepted", header);
+        }
+    }
+
+    #[test]
+    fn test_invalid_ranges() {
+        for header in &[
+            "",
+            "bytes",
+            "=0-5",
+            "bytes=",
+            "bytes=,",
+            "bytes=-",
+            "bytes=5",
+            "bytes=a-5",
+            "bytes=+1-5",
+            "bytes=0-5-9",
+            "bytes=0-99999999999999999999",
+        ] {
+            assert!(header.parse::<Range>().is_err(), "{:?} was accepted", header);
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(ByteRange::FromTo(0, 1000).resolve(12), Some((0, 11)));
+        assert_eq!(ByteRange::FromTo(6, 10).resolve(12), Some((6, 10)));
+        assert_eq!(ByteRange::FromTo(12, 20).resolve(12), None);
+        assert_eq!(ByteRange::From(11).resolve(12), Some((11, 11)));
+        assert_eq!(ByteRange::From(12).resolve(12), None);
+        assert_eq!(ByteRange::Suffix(5).resolve(12), Some((7, 11)));
+        assert_eq!(ByteRange::Suffix(50).resolve(12), Some((0, 11)));
+        assert_eq!(ByteRange::Suffix(0).resolve(12), None);
+        assert_eq!(ByteRange::From(0).resolve(0), None);
+
+        assert_eq!(parse("bytes=6-").single(), Some(ByteRange::From(6)));
+        assert_eq!(parse("bytes=0-1,4-5").single(), None);
+    }
+}
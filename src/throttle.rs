@@ -0,0 +1,73 @@
+//! Paces response bodies to a bandwidth, e.g. to test how clients deal with slow downloads.
+
+use rocket::tokio::time::{sleep_until, Instant, Sleep};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Wraps the body of a response and hands out no more than `bytes_per_sec` of it per second,
+/// in chunks of a tenth of that so the pace stays even.
+pub(crate) struct ThrottledReader<R> {
+    inner: R,
+    bytes_per_sec: u64,
+    started: Option<Instant>,
+    sent: u64,
+    delay: Option<Pin<Box<Sleep>>>,
+    chunk: Vec<u8>,
+}
+
+impl<R> ThrottledReader<R> {
+    pub(crate) fn new(inner: R, bytes_per_sec: u64) -> Self {
+        ThrottledReader {
+            inner,
+            bytes_per_sec,
+            started: None,
+            sent: 0,
+            delay: None,
+            chunk: vec![0; (bytes_per_sec / 10).clamp(1, 64 * 1024) as usize],
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ThrottledReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        // The clock starts with the first read, not when the response was put together
+        let started = *this.started.get_or_insert_with(Instant::now);
+
+        loop {
+            if let Some(delay) = this.delay.as_mut() {
+                if delay.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                this.delay = None;
+            }
+
+            // The bytes sent so far are due at this point, nothing more may go out before it
+            let due =
+                started + Duration::from_secs_f64(this.sent as f64 / this.bytes_per_sec as f64);
+            if due <= Instant::now() {
+                break;
+            }
+            this.delay = Some(Box::pin(sleep_until(due)));
+        }
+
+        let len = this.chunk.len().min(buf.remaining());
+        let mut chunk = ReadBuf::new(&mut this.chunk[..len]);
+        match Pin::new(&mut this.inner).poll_read(cx, &mut chunk) {
+            Poll::Ready(Ok(())) => {
+                this.sent += chunk.filled().len() as u64;
+                buf.put_slice(chunk.filled());
+                Poll::Ready(Ok(()))
+            }
+            poll => poll,
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Measures how long serving a file took, to point out slow assets.
+
+use crate::SlowFileWarning;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Wraps the reader of a served file and reports the file to the `SlowFileWarning` if the
+/// threshold has passed since `started` once it's done with, e.g. because the client downloads
+/// slowly or the file took long to open.
+pub(crate) struct TimedReader<R> {
+    inner: R,
+    path: String,
+    started: Instant,
+    warning: SlowFileWarning,
+}
+
+impl<R> TimedReader<R> {
+    pub(crate) fn new(inner: R, path: &str, started: Instant, warning: SlowFileWarning) -> Self {
+        TimedReader {
+            inner,
+            path: path.to_string(),
+            started,
+            warning,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for TimedReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<R> Drop for TimedReader<R> {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        if elapsed > self.warning.threshold() {
+            self.warning.report(&self.path, elapsed);
+        }
+    }
+}